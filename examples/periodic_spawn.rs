@@ -1,8 +1,6 @@
 use bevy::prelude::*;
-use bevy::utils::HashMap;
-use zeal::bubbles::{BubbleBundleBuilder, BubbleId, Bubbles};
-use zeal::physics::GlobalPhysics;
-use zeal::{default_app, GraphBubble};
+use zeal::bubbles::{BubbleCommand, BubbleEvent, BubbleId};
+use zeal::default_app;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut app = create_app();
@@ -31,51 +29,27 @@ impl Default for Countdown {
     }
 }
 
-static mut MAIN_BUBBLE_ID: Option<BubbleId> = None;
-
-fn extra_setup(
-    mut bubbles: ResMut<Bubbles>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    // Make a bubble
-    let mut graph = BubbleBundleBuilder::new(&mut commands, &mut meshes, &mut materials);
-    let id = bubbles.spawn_orphan(Vec2::ZERO, &mut graph);
-
-    unsafe {
-        let _ = MAIN_BUBBLE_ID.insert(id);
-    };
+fn extra_setup(mut bubble_commands: EventWriter<BubbleCommand>) {
+    bubble_commands.send(BubbleCommand::SpawnOrphan(Vec2::ZERO));
 }
 
 fn countdown(
     time: Res<Time>,
     mut countdown: ResMut<Countdown>,
-    mut bubbles: ResMut<Bubbles>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut physics: ResMut<GlobalPhysics>,
-    q: Query<(&GraphBubble, &Transform)>,
+    mut spawned: EventReader<BubbleEvent>,
+    mut bubble_commands: EventWriter<BubbleCommand>,
+    mut main_bubble: Local<Option<BubbleId>>,
 ) {
-    let pos_by_id = q
-        .iter()
-        .map(|(id, tfm)| (id.0, tfm.translation.truncate()))
-        .collect::<HashMap<BubbleId, Vec2>>();
-
-    let mut builder = BubbleBundleBuilder::from_positions_by_id(
-        pos_by_id,
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-    );
+    // The first bubble spawned is the one from `extra_setup`
+    for ev in spawned.iter() {
+        if let BubbleEvent::Spawned { id, .. } = ev {
+            main_bubble.get_or_insert(*id);
+        }
+    }
 
     if countdown.timer.tick(time.delta()).just_finished() {
-        if let Some(prime) = unsafe { MAIN_BUBBLE_ID } {
-            // Unwrap is safe because we know that the prime bubble was spawned in init
-            bubbles
-                .spawn_child(prime, &mut builder, &mut physics)
-                .unwrap();
+        if let Some(prime) = *main_bubble {
+            bubble_commands.send(BubbleCommand::SpawnChild(prime));
         }
     }
 }
@@ -0,0 +1,97 @@
+//! Times the physics update on large maps with the serial and the parallel path, and checks that
+//! both produce identical results
+//!
+//! Run with `cargo run --release --example physics_bench`
+use std::time::{Duration, Instant};
+
+use bevy::{core::TaskPoolPlugin, prelude::*};
+use rand::Rng;
+use zeal::{
+    bubbles::{BubbleBundleBuilder, Bubbles, GraphPlugin},
+    physics::{GlobalPhysics, PhysicsPlugin},
+    rng::SeededRng,
+    GraphBubble,
+};
+
+const SIZES: [usize; 4] = [100, 500, 1000, 2000];
+const FRAMES: u32 = 30;
+const FRAME_TIME: Duration = Duration::from_millis(16);
+const SEED: u64 = 1;
+
+#[derive(Resource)]
+struct MapSize(usize);
+
+fn main() {
+    println!(
+        "{:>8} {:>12} {:>12} {:>8} {:>10}",
+        "bubbles", "serial", "parallel", "speedup", "identical"
+    );
+    for n in SIZES {
+        let (serial_time, serial_positions) = run(n, false);
+        let (parallel_time, parallel_positions) = run(n, true);
+        println!(
+            "{:>8} {:>10.2?} {:>10.2?} {:>7.2}x {:>10}",
+            n,
+            serial_time / FRAMES,
+            parallel_time / FRAMES,
+            serial_time.as_secs_f64() / parallel_time.as_secs_f64(),
+            serial_positions == parallel_positions
+        );
+    }
+}
+
+/// Simulates a random tree of `n` bubbles for [`FRAMES`] frames
+///
+/// Returns the time spent per frame and the final position of each bubble, ordered by ID.
+fn run(n: usize, parallel: bool) -> (Duration, Vec<Vec2>) {
+    let mut app = App::new();
+    // Not `headless_app`, whose `TimePlugin` would advance the time by the real frame time
+    app.add_plugins((TaskPoolPlugin::default(), GraphPlugin, PhysicsPlugin))
+        .insert_resource(SeededRng::from_seed(SEED))
+        .insert_resource(MapSize(n))
+        .init_resource::<Time>()
+        .add_systems(Startup, spawn_tree);
+    app.world.resource_mut::<GlobalPhysics>().parallel = parallel;
+
+    // Simulate with a fixed time step, starting from an arbitrary instant
+    let mut now = Instant::now();
+    app.world.resource_mut::<Time>().update_with_instant(now);
+    // The first update only spawns the map
+    app.update();
+
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..FRAMES {
+        now += FRAME_TIME;
+        app.world.resource_mut::<Time>().update_with_instant(now);
+
+        let start = Instant::now();
+        app.update();
+        elapsed += start.elapsed();
+    }
+
+    let mut positions = app
+        .world
+        .query::<(&GraphBubble, &Transform)>()
+        .iter(&app.world)
+        .map(|(id, tfm)| (id.0, tfm.translation.truncate()))
+        .collect::<Vec<_>>();
+    positions.sort_by_key(|(id, _)| *id);
+    (elapsed, positions.into_iter().map(|(_, pos)| pos).collect())
+}
+
+fn spawn_tree(
+    size: Res<MapSize>,
+    mut bubbles: ResMut<Bubbles>,
+    mut rng: ResMut<SeededRng>,
+    mut commands: Commands,
+) {
+    let mut builder = BubbleBundleBuilder::new(&mut commands);
+    let mut ids = vec![bubbles.spawn_orphan(Vec2::ZERO, &mut builder)];
+    while ids.len() < size.0 {
+        let parent = ids[rng.gen_range(0..ids.len())];
+        let child = bubbles
+            .spawn_child(parent, &mut builder, rng.as_mut())
+            .unwrap();
+        ids.push(child);
+    }
+}
@@ -0,0 +1,100 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    bubbles::graph::{BubbleId, EdgeKind},
+    physics::BubblePhysics,
+    BubbleConnection, GraphBubble,
+};
+
+/// Size of the ellipse drawn for each bubble, in pixels
+pub const BUBBLE_SIZE: Vec2 = Vec2::new(100., 50.);
+
+/// Color of new bubbles
+pub const DEFAULT_BUBBLE_COLOR: Color = Color::PURPLE;
+
+/// Text of a bubble
+#[derive(Component, Clone, Debug)]
+pub struct BubbleContent(pub String);
+
+/// Fill color of a bubble
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BubbleColor(pub Color);
+
+/// Spawns the entities of bubbles and connections
+///
+/// Only the components needed by the graph and the physics are spawned. The rendering plugin adds
+/// the visuals once the entities exist.
+pub struct BubbleBundleBuilder<'c, 'w, 's> {
+    commands: &'c mut Commands<'w, 's>,
+    positions_by_id: HashMap<BubbleId, Vec2>,
+}
+
+impl<'c, 'w, 's> BubbleBundleBuilder<'c, 'w, 's> {
+    pub fn new(commands: &'c mut Commands<'w, 's>) -> Self {
+        Self::from_positions_by_id(HashMap::new(), commands)
+    }
+
+    pub fn from_positions_by_id(
+        positions_by_id: HashMap<BubbleId, Vec2>,
+        commands: &'c mut Commands<'w, 's>,
+    ) -> Self {
+        Self {
+            commands,
+            positions_by_id,
+        }
+    }
+
+    /// Spawns the bubble and returns its entity
+    pub fn create_bubble(&mut self, id: BubbleId, pos: Vec2) -> Entity {
+        let content = BubbleContent(id.to_string());
+        let physics = BubblePhysics::from_content(content.0.len(), 0);
+        self.positions_by_id.insert(id, pos);
+        self.commands
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(pos.extend(1.))),
+                physics,
+                GraphBubble(id),
+                content,
+                BubbleColor(DEFAULT_BUBBLE_COLOR),
+            ))
+            .id()
+    }
+
+    /// Creates a connection between the `left` bubble and the `right` bubble
+    ///
+    /// Returns the entity of the connection.
+    pub fn connect(&mut self, left: BubbleId, right: BubbleId) -> Entity {
+        self.commands
+            .spawn((BubbleConnection(left, right), EdgeKind::default()))
+            .id()
+    }
+
+    /// Replaces the content and color of a bubble spawned by this builder
+    ///
+    /// Unlike editing the components, this works before the bubble exists in the world.
+    pub fn set_style(&mut self, entity: Entity, content: &str, color: Color) {
+        self.commands
+            .entity(entity)
+            .insert((BubbleContent(content.to_string()), BubbleColor(color)));
+    }
+
+    /// Commands used for spawning, e.g., for despawning entities while the builder is alive
+    pub fn commands(&mut self) -> &mut Commands<'w, 's> {
+        self.commands
+    }
+
+    /// Updates the known position of a bubble that was moved
+    pub fn set_position(&mut self, bubble: BubbleId, pos: Vec2) {
+        self.positions_by_id.insert(bubble, pos);
+    }
+
+    /// Returns the position of the bubble, or `None` if it is not known to the builder
+    pub fn position(&self, bubble: BubbleId) -> Option<Vec2> {
+        self.positions_by_id.get(&bubble).copied()
+    }
+
+    /// Positions of all known bubbles
+    pub fn positions(&self) -> impl Iterator<Item = (BubbleId, Vec2)> + '_ {
+        self.positions_by_id.iter().map(|(id, pos)| (*id, *pos))
+    }
+}
@@ -0,0 +1,38 @@
+//! Copies of parts of the graph, for pasting them elsewhere
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::EdgeKind;
+
+/// A bubble in a [`SubtreeCopy`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CopiedBubble {
+    pub content: String,
+    pub color: Color,
+    /// Position relative to the root of the copy
+    pub offset: Vec2,
+}
+
+/// A copy of a bubble and its descendants, made by [`Bubbles::copy_subtree`](super::Bubbles::copy_subtree)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubtreeCopy {
+    /// The copied bubbles, the root first. Never empty.
+    pub(super) bubbles: Vec<CopiedBubble>,
+    /// Connections between the copied bubbles as `(from, to, kind)`, indexing `bubbles`
+    pub(super) edges: Vec<(usize, usize, EdgeKind)>,
+}
+
+impl SubtreeCopy {
+    pub fn len(&self) -> usize {
+        self.bubbles.len()
+    }
+
+    /// Always false, a copy contains at least its root
+    pub fn is_empty(&self) -> bool {
+        self.bubbles.is_empty()
+    }
+
+    pub fn root(&self) -> &CopiedBubble {
+        &self.bubbles[0]
+    }
+}
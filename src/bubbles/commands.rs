@@ -0,0 +1,246 @@
+//! Event interface for editing the bubble graph
+//!
+//! Systems send [`BubbleCommand`]s and [`apply_bubble_commands`] carries them out, so editing the
+//! graph does not require access to the render assets.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BubbleBundleBuilder, BubbleColor, BubbleContent, BubbleGraphError, BubbleId, Bubbles, EdgeId,
+    EdgeKind, SubtreeCopy,
+};
+use crate::{
+    layout::{Convergence, LayoutRun},
+    physics::{BubblePhysics, GlobalPhysics},
+    rng::SeededRng,
+    GraphBubble,
+};
+
+/// A request to change the bubble graph
+#[derive(Event, Debug, Clone, Serialize, Deserialize)]
+pub enum BubbleCommand {
+    /// Spawn a bubble at the given position
+    SpawnOrphan(Vec2),
+    /// Spawn a bubble connected to the given parent, placed next to it
+    SpawnChild(BubbleId),
+    /// Spawn a bubble connected to the given parent at the given position
+    SpawnChildAt(BubbleId, Vec2),
+    /// Connect the first bubble to the second
+    Connect(BubbleId, BubbleId),
+    /// Remove the connection from the first bubble to the second
+    Disconnect(BubbleId, BubbleId),
+    /// Remove the connection
+    RemoveEdge(EdgeId),
+    /// Turn the connection around
+    ReverseEdge(EdgeId),
+    /// Despawn the bubble and its connections
+    Remove(BubbleId),
+    /// Replace the text of the bubble
+    SetContent(BubbleId, String),
+    /// Move the bubble to the given position and stop it
+    Move(BubbleId, Vec2),
+    /// Connect the bubbles to a new bubble spawned in their middle
+    Group(Vec<BubbleId>),
+    /// Change the fill color of the bubble
+    SetColor(BubbleId, Color),
+    /// Keep the bubble in place regardless of forces, or release it
+    SetPinned(BubbleId, bool),
+    /// Change what the connection means
+    SetEdgeKind(EdgeId, EdgeKind),
+    /// Spawn a copy of the subtree with its root as a child of the bubble
+    Paste(BubbleId, SubtreeCopy),
+    /// Move every bubble to where the physics would settle it, see [`Settling`]
+    Settle,
+}
+
+/// Iterations of the [`Settling`] layout run per frame
+///
+/// A fixed number, rather than a time budget, settles replayed sessions on the same frames.
+const SETTLE_ITERATIONS_PER_FRAME: usize = 200;
+
+/// The layout run started by the last [`BubbleCommand::Settle`], advanced a bit on each frame by
+/// [`settle_bubbles`] so that big graphs do not freeze the app
+///
+/// The run works on the graph as it was when the command was applied. Bubbles spawned meanwhile
+/// stay where they are.
+#[derive(Resource)]
+pub struct Settling(LayoutRun);
+
+/// Advances the [`Settling`] run and moves the bubbles once it is done
+pub fn settle_bubbles(
+    mut commands: Commands,
+    settling: Option<ResMut<Settling>>,
+    gphysics: Option<Res<GlobalPhysics>>,
+    mut q: Query<(&mut Transform, &mut BubblePhysics, &GraphBubble)>,
+) {
+    let Some(mut settling) = settling else {
+        return;
+    };
+    // Without the physics plugin, settle with the default forces
+    let default_physics = GlobalPhysics::default();
+    let gphysics = gphysics.as_deref().unwrap_or(&default_physics);
+    if !settling.0.advance(gphysics, SETTLE_ITERATIONS_PER_FRAME) {
+        return;
+    }
+
+    let layout = settling.0.layout();
+    for (mut tfm, mut physics, id) in q.iter_mut() {
+        if let Some(pos) = layout.positions.get(&id.0) {
+            tfm.translation = pos.extend(tfm.translation.z);
+            physics.vel = Vec2::ZERO;
+        }
+    }
+    commands.remove_resource::<Settling>();
+}
+
+/// Changes made by [`apply_bubble_commands`]
+#[derive(Event, Debug, Clone)]
+pub enum BubbleEvent {
+    /// A bubble was spawned, connected to `parent` if one was given
+    Spawned {
+        id: BubbleId,
+        parent: Option<BubbleId>,
+    },
+    /// The command was applied, sent after the events it caused, e.g., for logging sessions
+    Applied(BubbleCommand),
+    /// The command could not be applied and was skipped
+    Failed {
+        command: BubbleCommand,
+        error: BubbleGraphError,
+    },
+}
+
+type BubbleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut BubblePhysics,
+        &'static mut BubbleContent,
+        &'static mut BubbleColor,
+        &'static GraphBubble,
+    ),
+>;
+
+/// Applies the [`BubbleCommand`]s sent since the last run, in order
+///
+/// Commands that fail are skipped and reported as [`BubbleEvent::Failed`]. Bubbles spawned by
+/// this run cannot be moved or edited until the next run.
+pub fn apply_bubble_commands(
+    mut requests: EventReader<BubbleCommand>,
+    mut events: EventWriter<BubbleEvent>,
+    mut bubbles: ResMut<Bubbles>,
+    mut rng: ResMut<SeededRng>,
+    mut commands: Commands,
+    mut q: BubbleQuery,
+) {
+    if requests.is_empty() {
+        return;
+    }
+
+    let positions = q
+        .iter()
+        .map(|(tfm, _, _, _, id)| (id.0, tfm.translation.truncate()))
+        .collect();
+    let mut builder = BubbleBundleBuilder::from_positions_by_id(positions, &mut commands);
+
+    for request in requests.iter() {
+        let result = match request {
+            BubbleCommand::SpawnOrphan(pos) => {
+                let id = bubbles.spawn_orphan(*pos, &mut builder);
+                events.send(BubbleEvent::Spawned { id, parent: None });
+                Ok(())
+            }
+            BubbleCommand::SpawnChild(parent) => bubbles
+                .spawn_child(*parent, &mut builder, rng.as_mut())
+                .map(|id| {
+                    events.send(BubbleEvent::Spawned {
+                        id,
+                        parent: Some(*parent),
+                    })
+                }),
+            BubbleCommand::SpawnChildAt(parent, pos) => bubbles
+                .spawn_child_at(*parent, *pos, &mut builder)
+                .map(|id| {
+                    events.send(BubbleEvent::Spawned {
+                        id,
+                        parent: Some(*parent),
+                    })
+                }),
+            BubbleCommand::Connect(from, to) => {
+                bubbles.connect(*from, *to, &mut builder).map(|_| ())
+            }
+            BubbleCommand::Disconnect(from, to) => {
+                bubbles.disconnect(*from, *to, builder.commands())
+            }
+            BubbleCommand::RemoveEdge(edge) => bubbles.remove_edge(*edge, builder.commands()),
+            BubbleCommand::ReverseEdge(edge) => {
+                bubbles.reverse_edge(*edge, builder.commands()).map(|_| ())
+            }
+            BubbleCommand::Remove(id) => bubbles.despawn(*id, builder.commands()),
+            BubbleCommand::SetContent(id, text) => bubbles
+                .entity(*id)
+                .and_then(|e| q.get_mut(e).ok())
+                .map(|(_, _, mut content, _, _)| content.0 = text.clone())
+                .ok_or(BubbleGraphError::NotPresent(*id)),
+            BubbleCommand::Move(id, pos) => bubbles
+                .entity(*id)
+                .and_then(|e| q.get_mut(e).ok())
+                .map(|(mut tfm, mut physics, _, _, _)| {
+                    tfm.translation = pos.extend(tfm.translation.z);
+                    physics.vel = Vec2::ZERO;
+                    builder.set_position(*id, *pos);
+                })
+                .ok_or(BubbleGraphError::NotPresent(*id)),
+            BubbleCommand::Group(children) => bubbles
+                .group(children, &mut builder)
+                .map(|id| events.send(BubbleEvent::Spawned { id, parent: None })),
+            BubbleCommand::SetColor(id, color) => bubbles
+                .entity(*id)
+                .and_then(|e| q.get_mut(e).ok())
+                .map(|(_, _, _, mut bubble_color, _)| bubble_color.0 = *color)
+                .ok_or(BubbleGraphError::NotPresent(*id)),
+            BubbleCommand::SetPinned(id, pinned) => bubbles
+                .entity(*id)
+                .and_then(|e| q.get_mut(e).ok())
+                .map(|(_, mut physics, _, _, _)| {
+                    physics.pinned = *pinned;
+                    physics.vel = Vec2::ZERO;
+                })
+                .ok_or(BubbleGraphError::NotPresent(*id)),
+            BubbleCommand::SetEdgeKind(edge, kind) => {
+                bubbles.set_edge_kind(*edge, *kind, builder.commands())
+            }
+            BubbleCommand::Paste(parent, copy) => bubbles
+                .paste(*parent, copy, &mut builder, rng.as_mut())
+                .map(|id| {
+                    events.send(BubbleEvent::Spawned {
+                        id,
+                        parent: Some(*parent),
+                    })
+                }),
+            BubbleCommand::Settle => {
+                let initial = q
+                    .iter()
+                    .map(|(tfm, physics, _, _, id)| {
+                        (id.0, (tfm.translation.truncate(), physics.clone()))
+                    })
+                    .collect();
+                let run = LayoutRun::new(&initial, &bubbles.edges(), Convergence::default());
+                builder.commands().insert_resource(Settling(run));
+                Ok(())
+            }
+        };
+
+        match result {
+            Ok(()) => events.send(BubbleEvent::Applied(request.clone())),
+            Err(error) => {
+                warn!("Cannot apply {request:?}: {error}");
+                events.send(BubbleEvent::Failed {
+                    command: request.clone(),
+                    error,
+                });
+            }
+        }
+    }
+}
@@ -1,16 +1,66 @@
-use bevy::prelude::*;
-use petgraph::stable_graph::StableDiGraph;
+use bevy::{prelude::*, utils::HashMap};
+use petgraph::visit::EdgeRef;
+use petgraph::{
+    stable_graph::{EdgeIndex, StableDiGraph},
+    unionfind::UnionFind,
+    Direction,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 pub type BubbleId = u32;
+/// Identifies a connection between two bubbles
+///
+/// IDs of removed connections are reused, and reversing a connection gives it a new ID. Check
+/// [`BubbleGraph::edge_endpoints`] before acting on an ID that was stored across frames.
+pub type EdgeId = u32;
+/// Identifies a connected component of the graph, i.e., a set of bubbles that are linked to each
+/// other but not to any bubble outside the set
+pub type SubgraphId = usize;
 
 #[derive(Resource, Default)]
 pub struct BubbleGraph {
-    graph: StableDiGraph<BubbleId, ()>,
+    graph: StableDiGraph<BubbleId, EdgeKind>,
 }
 
-#[derive(Debug)]
+/// What a connection means, shown by its color
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EdgeKind {
+    /// The source is a child of the target
+    #[default]
+    Hierarchy,
+    /// The source refers to the target
+    Reference,
+    /// The source comes before the target
+    Sequence,
+}
+
+impl EdgeKind {
+    pub const ALL: [EdgeKind; 3] = [EdgeKind::Hierarchy, EdgeKind::Reference, EdgeKind::Sequence];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::Hierarchy => "hierarchy",
+            EdgeKind::Reference => "reference",
+            EdgeKind::Sequence => "sequence",
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum BubbleGraphError {
-    NotPresent,
+    #[error("bubble {0} does not exist")]
+    NotPresent(BubbleId),
+    #[error("bubble {0} has no known position")]
+    NoPosition(BubbleId),
+    #[error("there is no connection from bubble {0} to bubble {1}")]
+    NoConnection(BubbleId, BubbleId),
+    #[error("connection {0} does not exist")]
+    NoEdge(EdgeId),
+    #[error("bubble {0} cannot be connected to itself")]
+    SelfLoop(BubbleId),
+    #[error("bubbles {0} and {1} are already connected")]
+    AlreadyConnected(BubbleId, BubbleId),
 }
 
 impl BubbleGraph {
@@ -19,30 +69,241 @@ impl BubbleGraph {
         uuid.index() as BubbleId
     }
 
-    pub fn remove(&mut self, id: BubbleId) -> Option<u32> {
-        self.graph.remove_node(id.into())
+    /// Removes the bubble and all of its edges
+    pub fn remove(&mut self, id: BubbleId) -> Result<(), BubbleGraphError> {
+        self.graph
+            .remove_node(id.into())
+            .map(|_| ())
+            .ok_or(BubbleGraphError::NotPresent(id))
     }
 
-    /// Returns error if either node did not exist
-    pub fn add_edge(&mut self, left: BubbleId, right: BubbleId) -> Result<(), BubbleGraphError> {
-        self.graph.add_edge(left.into(), right.into(), ());
+    /// Adds an edge from `left` to `right`
+    ///
+    /// Returns error if either node did not exist, if they are the same node, or if they are already
+    /// connected in either direction.
+    pub fn add_edge(
+        &mut self,
+        left: BubbleId,
+        right: BubbleId,
+    ) -> Result<EdgeId, BubbleGraphError> {
+        self.check_node(left)?;
+        self.check_node(right)?;
+        if left == right {
+            return Err(BubbleGraphError::SelfLoop(left));
+        }
+        if self.graph.contains_edge(left.into(), right.into())
+            || self.graph.contains_edge(right.into(), left.into())
+        {
+            return Err(BubbleGraphError::AlreadyConnected(left, right));
+        }
+        let edge = self
+            .graph
+            .add_edge(left.into(), right.into(), EdgeKind::default());
+        Ok(edge.index() as EdgeId)
+    }
+
+    /// Removes the edge and returns its endpoints as `(from, to)`
+    pub fn remove_edge(&mut self, edge: EdgeId) -> Result<(BubbleId, BubbleId), BubbleGraphError> {
+        let endpoints = self.edge_endpoints(edge)?;
+        self.graph.remove_edge(EdgeIndex::new(edge as usize));
+        Ok(endpoints)
+    }
+
+    /// Replaces the edge with one in the opposite direction and returns the ID of the new edge
+    ///
+    /// The kind of the edge is kept.
+    pub fn reverse_edge(&mut self, edge: EdgeId) -> Result<EdgeId, BubbleGraphError> {
+        let kind = self.edge_kind(edge)?;
+        let (from, to) = self.remove_edge(edge)?;
+        let reversed = self.add_edge(to, from)?;
+        self.set_edge_kind(reversed, kind)?;
+        Ok(reversed)
+    }
+
+    pub fn edge_kind(&self, edge: EdgeId) -> Result<EdgeKind, BubbleGraphError> {
+        self.graph
+            .edge_weight(EdgeIndex::new(edge as usize))
+            .copied()
+            .ok_or(BubbleGraphError::NoEdge(edge))
+    }
 
+    pub fn set_edge_kind(&mut self, edge: EdgeId, kind: EdgeKind) -> Result<(), BubbleGraphError> {
+        let weight = self
+            .graph
+            .edge_weight_mut(EdgeIndex::new(edge as usize))
+            .ok_or(BubbleGraphError::NoEdge(edge))?;
+        *weight = kind;
         Ok(())
     }
 
-    pub fn remove_edge(&mut self, id: BubbleId) -> Option<()> {
-        self.graph.remove_edge(id.into())
+    /// Returns the edge from `from` to `to`
+    pub fn find_edge(&self, from: BubbleId, to: BubbleId) -> Result<EdgeId, BubbleGraphError> {
+        self.check_node(from)?;
+        self.check_node(to)?;
+        self.graph
+            .find_edge(from.into(), to.into())
+            .map(|e| e.index() as EdgeId)
+            .ok_or(BubbleGraphError::NoConnection(from, to))
     }
 
-    pub fn neighbors(&self, idx: BubbleId) -> Vec<BubbleId> {
+    /// Returns the endpoints of the edge as `(from, to)`
+    pub fn edge_endpoints(&self, edge: EdgeId) -> Result<(BubbleId, BubbleId), BubbleGraphError> {
         self.graph
+            .edge_endpoints(EdgeIndex::new(edge as usize))
+            .map(|(from, to)| (from.index() as BubbleId, to.index() as BubbleId))
+            .ok_or(BubbleGraphError::NoEdge(edge))
+    }
+
+    /// Returns the edges that end at `idx`
+    pub fn incoming(&self, idx: BubbleId) -> Result<Vec<EdgeId>, BubbleGraphError> {
+        self.edges_directed(idx, Direction::Incoming)
+    }
+
+    /// Returns the edges that start from `idx`
+    pub fn outgoing(&self, idx: BubbleId) -> Result<Vec<EdgeId>, BubbleGraphError> {
+        self.edges_directed(idx, Direction::Outgoing)
+    }
+
+    fn edges_directed(
+        &self,
+        idx: BubbleId,
+        dir: Direction,
+    ) -> Result<Vec<EdgeId>, BubbleGraphError> {
+        self.check_node(idx)?;
+        Ok(self
+            .graph
+            .edges_directed(idx.into(), dir)
+            .map(|e| e.id().index() as EdgeId)
+            .collect())
+    }
+
+    pub fn neighbors(&self, idx: BubbleId) -> Result<Vec<BubbleId>, BubbleGraphError> {
+        self.check_node(idx)?;
+        Ok(self
+            .graph
             .neighbors(idx.into())
-            .into_iter()
             .map(|x| x.index() as u32)
-            .collect()
+            .collect())
+    }
+
+    /// Returns the bubbles connected to `idx`, i.e., its children
+    pub fn children(&self, idx: BubbleId) -> Result<Vec<BubbleId>, BubbleGraphError> {
+        self.check_node(idx)?;
+        Ok(self
+            .graph
+            .neighbors_directed(idx.into(), Direction::Incoming)
+            .map(|x| x.index() as u32)
+            .collect())
+    }
+
+    /// Number of connections of `idx` in either direction, 0 if it does not exist
+    pub fn degree(&self, idx: BubbleId) -> usize {
+        if !self.contains_node(idx) {
+            return 0;
+        }
+        self.graph
+            .edges_directed(idx.into(), Direction::Outgoing)
+            .count()
+            + self
+                .graph
+                .edges_directed(idx.into(), Direction::Incoming)
+                .count()
+    }
+
+    /// Returns both the parents and the children of `idx`
+    pub fn neighbors_undirected(&self, idx: BubbleId) -> Result<Vec<BubbleId>, BubbleGraphError> {
+        self.check_node(idx)?;
+        Ok(self
+            .graph
+            .neighbors_undirected(idx.into())
+            .map(|x| x.index() as u32)
+            .collect())
+    }
+
+    /// Returns error if the node does not exist
+    pub fn check_node(&self, idx: BubbleId) -> Result<(), BubbleGraphError> {
+        if self.contains_node(idx) {
+            Ok(())
+        } else {
+            Err(BubbleGraphError::NotPresent(idx))
+        }
     }
 
     pub fn contains_node(&self, idx: BubbleId) -> bool {
         self.graph.contains_node(idx.into())
     }
+
+    pub fn ids(&self) -> Vec<BubbleId> {
+        self.graph
+            .node_indices()
+            .map(|x| x.index() as BubbleId)
+            .collect()
+    }
+
+    /// Returns the connected component that each bubble belongs to
+    pub fn subgraphs(&self) -> HashMap<BubbleId, SubgraphId> {
+        connected_components(&self.ids(), &self.edges())
+    }
+
+    /// Returns the endpoints of each edge as `(from, to)`
+    pub fn edge_ids(&self) -> Vec<EdgeId> {
+        self.graph
+            .edge_indices()
+            .map(|e| e.index() as EdgeId)
+            .collect()
+    }
+
+    pub fn edges(&self) -> Vec<(BubbleId, BubbleId)> {
+        self.graph
+            .edge_indices()
+            .filter_map(|e| self.graph.edge_endpoints(e))
+            .map(|(from, to)| (from.index() as BubbleId, to.index() as BubbleId))
+            .collect()
+    }
+}
+
+/// Assigns each bubble in `ids` to a connected component, ignoring edge direction
+///
+/// Edges to bubbles not in `ids` are ignored.
+pub(crate) fn connected_components(
+    ids: &[BubbleId],
+    edges: &[(BubbleId, BubbleId)],
+) -> HashMap<BubbleId, SubgraphId> {
+    let idx_by_id = ids
+        .iter()
+        .enumerate()
+        .map(|(idx, id)| (*id, idx))
+        .collect::<HashMap<_, _>>();
+
+    let mut sets = UnionFind::new(ids.len());
+    for (from, to) in edges {
+        if let (Some(from), Some(to)) = (idx_by_id.get(from), idx_by_id.get(to)) {
+            sets.union(*from, *to);
+        }
+    }
+
+    // The representative of each set serves as the component ID
+    ids.iter()
+        .enumerate()
+        .map(|(idx, id)| (*id, sets.find(idx)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connected_components_ignore_direction_and_unknown_bubbles() {
+        let ids = [0, 1, 2, 3, 4];
+        let edges = [(0, 1), (2, 1), (3, 9)];
+        let components = connected_components(&ids, &edges);
+
+        assert_eq!(components.len(), ids.len());
+        assert_eq!(components[&0], components[&1]);
+        assert_eq!(components[&1], components[&2]);
+        assert_ne!(components[&0], components[&3]);
+        assert_ne!(components[&0], components[&4]);
+        assert_ne!(components[&3], components[&4]);
+    }
 }
@@ -1,73 +1,462 @@
+mod builder;
+mod clipboard;
+mod commands;
 mod graph;
-mod visuals;
+mod placement;
+mod registry;
 
-pub use graph::BubbleId;
-pub use visuals::BubbleBundleBuilder;
+pub use builder::{
+    BubbleBundleBuilder, BubbleColor, BubbleContent, BUBBLE_SIZE, DEFAULT_BUBBLE_COLOR,
+};
+pub use clipboard::{CopiedBubble, SubtreeCopy};
+pub use commands::{apply_bubble_commands, settle_bubbles, BubbleCommand, BubbleEvent, Settling};
+pub(crate) use graph::connected_components;
+pub use graph::{BubbleGraphError, BubbleId, EdgeId, EdgeKind, SubgraphId};
+pub use registry::Inconsistency;
 
-use self::graph::BubbleGraphError;
-use crate::physics::{Force, GlobalPhysics};
-use bevy::prelude::*;
-use petgraph::stable_graph::IndexType;
-use rand::{thread_rng, Rng};
+use self::registry::EntityRegistry;
+use crate::{rng::SeededRng, BubbleConnection, GraphBubble};
+use bevy::{
+    ecs::query::ReadOnlyWorldQuery,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::Rng;
 // Do not expose graph::BubbleGraph. It is used through the `Bubbles` interface
 // that manages also the render graph.
 use graph::BubbleGraph;
 
+/// The bubble graph, [`Bubbles`], and the [`BubbleCommand`] interface for editing it
+pub struct GraphPlugin;
+
+impl Plugin for GraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BubbleCommand>()
+            .add_event::<BubbleEvent>()
+            .init_resource::<Bubbles>()
+            .init_resource::<SeededRng>()
+            .add_systems(Update, (settle_bubbles, apply_bubble_commands).chain());
+        #[cfg(debug_assertions)]
+        app.add_systems(Last, check_bubble_consistency);
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct Bubbles {
     graph: BubbleGraph,
+    /// Connected component of each bubble, updated whenever the graph changes
+    subgraphs: HashMap<BubbleId, SubgraphId>,
+    registry: EntityRegistry,
 }
 
 impl Bubbles {
     /// Spawns a bubble as a child to the given `parent`
     ///
-    /// Bubble's position depends on the other bubbles connected to the same parent.
+    /// Bubble is placed into the largest free space around the parent, away from the parent's
+    /// other neighbors and any bubbles nearby.
     ///
-    /// Returns error if the parent didn't exist.
+    /// Returns the ID of the new bubble, or error if the parent didn't exist or its position is not
+    /// known to `render_graph`.
     pub fn spawn_child(
         &mut self,
         parent: BubbleId,
         render_graph: &mut BubbleBundleBuilder,
-        physics: &mut GlobalPhysics,
-    ) -> Result<(), BubbleGraphError> {
-        let parent_pos = render_graph.position(parent);
-        let mut rng = thread_rng();
-        let pos = below(parent_pos, SPAWN_DIST) + Vec2::X * (50. * rng.gen::<f32>() - 25.);
+        rng: &mut impl Rng,
+    ) -> Result<BubbleId, BubbleGraphError> {
+        let neighbors = self.graph.neighbors_undirected(parent)?;
+        let parent_pos = render_graph
+            .position(parent)
+            .ok_or(BubbleGraphError::NoPosition(parent))?;
+        let (neighbor_positions, other_positions): (Vec<_>, Vec<_>) = render_graph
+            .positions()
+            .filter(|(id, _)| *id != parent)
+            .partition(|(id, _)| neighbors.contains(id));
+        let pos = placement::child_position(
+            parent_pos,
+            neighbor_positions.into_iter().map(|(_, pos)| pos),
+            other_positions.into_iter().map(|(_, pos)| pos),
+            SPAWN_DIST,
+            rng,
+        );
+        self.spawn_child_at(parent, pos, render_graph)
+    }
+
+    /// Spawns a bubble as a child to the given `parent` at the given position
+    ///
+    /// Returns the ID of the new bubble, or error if the parent didn't exist.
+    pub fn spawn_child_at(
+        &mut self,
+        parent: BubbleId,
+        pos: Vec2,
+        render_graph: &mut BubbleBundleBuilder,
+    ) -> Result<BubbleId, BubbleGraphError> {
+        self.graph.check_node(parent)?;
         let child = self.spawn_orphan(pos, render_graph);
+        self.connect(child, parent, render_graph)?;
+        Ok(child)
+    }
 
-        assert!(self.graph.contains_node(parent));
-        assert!(self.graph.contains_node(child));
-        self.graph.add_edge(child, parent)?;
-        render_graph.connect(child, parent);
+    /// Spawns a bubble in the middle of `children` and connects them to it
+    ///
+    /// Returns the ID of the new bubble, or error if any of the children didn't exist or its
+    /// position is not known to `render_graph`. Nothing is spawned on error.
+    pub fn group(
+        &mut self,
+        children: &[BubbleId],
+        render_graph: &mut BubbleBundleBuilder,
+    ) -> Result<BubbleId, BubbleGraphError> {
+        let mut sum = Vec2::ZERO;
+        for child in children {
+            self.graph.check_node(*child)?;
+            sum += render_graph
+                .position(*child)
+                .ok_or(BubbleGraphError::NoPosition(*child))?;
+        }
+        let centroid = sum / children.len().max(1) as f32;
 
-        physics.add_force(Force::Explosion {
-            origin: pos,
-            force: BUBBLE_SPAWN_FORCE,
-            r: BUBBLE_SPAWN_FORCE_DIST,
-        });
-        Ok(())
+        let parent = self.spawn_orphan(centroid, render_graph);
+        for child in children {
+            self.connect(*child, parent, render_graph)?;
+        }
+        Ok(parent)
     }
 
     /// Spawns an orphan bubble at given position
     pub fn spawn_orphan(&mut self, pos: Vec2, render_graph: &mut BubbleBundleBuilder) -> BubbleId {
         let id = self.graph.insert();
-        render_graph.create_bubble(id, pos);
+        self.subgraphs = self.graph.subgraphs();
+        let entity = render_graph.create_bubble(id, pos);
+        self.registry.insert(id, entity);
         id
     }
 
-    pub fn neighbors(&self, idx: BubbleId) -> Vec<BubbleId> {
-        self.graph
-            .neighbors(idx.into())
-            .into_iter()
-            .map(|x| x.index() as u32)
+    /// Connects `from` to `to` and returns the ID of the new edge
+    ///
+    /// Returns error if either bubble did not exist.
+    pub fn connect(
+        &mut self,
+        from: BubbleId,
+        to: BubbleId,
+        render_graph: &mut BubbleBundleBuilder,
+    ) -> Result<EdgeId, BubbleGraphError> {
+        let edge = self.graph.add_edge(from, to)?;
+        self.subgraphs = self.graph.subgraphs();
+        let connection = render_graph.connect(from, to);
+        self.registry.insert_connection(edge, connection);
+        Ok(edge)
+    }
+
+    /// Removes the connection from `from` to `to` and despawns its entity
+    ///
+    /// Returns error if there was no such connection.
+    pub fn disconnect(
+        &mut self,
+        from: BubbleId,
+        to: BubbleId,
+        commands: &mut Commands,
+    ) -> Result<(), BubbleGraphError> {
+        let edge = self.graph.find_edge(from, to)?;
+        self.remove_edge(edge, commands)
+    }
+
+    /// Removes the edge and despawns its connection entity
+    pub fn remove_edge(
+        &mut self,
+        edge: EdgeId,
+        commands: &mut Commands,
+    ) -> Result<(), BubbleGraphError> {
+        self.graph.remove_edge(edge)?;
+        self.subgraphs = self.graph.subgraphs();
+        if let Some(connection) = self.registry.remove_connection(edge) {
+            commands.entity(connection).despawn();
+        }
+        Ok(())
+    }
+
+    /// Turns the edge around and returns its new ID
+    ///
+    /// The connection entity is kept and updated to the new direction.
+    pub fn reverse_edge(
+        &mut self,
+        edge: EdgeId,
+        commands: &mut Commands,
+    ) -> Result<EdgeId, BubbleGraphError> {
+        let (from, to) = self.graph.edge_endpoints(edge)?;
+        let reversed = self.graph.reverse_edge(edge)?;
+        if let Some(connection) = self.registry.remove_connection(edge) {
+            commands
+                .entity(connection)
+                .insert(BubbleConnection(to, from));
+            self.registry.insert_connection(reversed, connection);
+        }
+        Ok(reversed)
+    }
+
+    /// Changes the kind of the edge, also on its connection entity
+    pub fn set_edge_kind(
+        &mut self,
+        edge: EdgeId,
+        kind: EdgeKind,
+        commands: &mut Commands,
+    ) -> Result<(), BubbleGraphError> {
+        self.graph.set_edge_kind(edge, kind)?;
+        if let Some(connection) = self.registry.connection(edge) {
+            commands.entity(connection).insert(kind);
+        }
+        Ok(())
+    }
+
+    pub fn edge_kind(&self, edge: EdgeId) -> Result<EdgeKind, BubbleGraphError> {
+        self.graph.edge_kind(edge)
+    }
+
+    /// Returns the edge from `from` to `to`
+    pub fn find_edge(&self, from: BubbleId, to: BubbleId) -> Result<EdgeId, BubbleGraphError> {
+        self.graph.find_edge(from, to)
+    }
+
+    /// Returns the endpoints of the edge as `(from, to)`
+    pub fn edge_endpoints(&self, edge: EdgeId) -> Result<(BubbleId, BubbleId), BubbleGraphError> {
+        self.graph.edge_endpoints(edge)
+    }
+
+    /// Returns the edges that end at the bubble, e.g., from its children
+    pub fn incoming_edges(&self, id: BubbleId) -> Result<Vec<EdgeId>, BubbleGraphError> {
+        self.graph.incoming(id)
+    }
+
+    /// Returns the edges that start from the bubble, e.g., to its parent
+    pub fn outgoing_edges(&self, id: BubbleId) -> Result<Vec<EdgeId>, BubbleGraphError> {
+        self.graph.outgoing(id)
+    }
+
+    /// Returns the entity that represents the edge
+    pub fn connection(&self, edge: EdgeId) -> Option<Entity> {
+        self.registry.connection(edge)
+    }
+
+    /// Removes the bubble from the graph and despawns it along with its connections
+    ///
+    /// Returns error if the bubble did not exist.
+    pub fn despawn(
+        &mut self,
+        id: BubbleId,
+        commands: &mut Commands,
+    ) -> Result<(), BubbleGraphError> {
+        let mut edges = self.graph.incoming(id)?;
+        edges.extend(self.graph.outgoing(id)?);
+        self.graph.remove(id)?;
+        self.subgraphs = self.graph.subgraphs();
+        if let Some(entity) = self.registry.remove(id) {
+            commands.entity(entity).despawn_recursive();
+        }
+        for edge in edges {
+            if let Some(connection) = self.registry.remove_connection(edge) {
+                commands.entity(connection).despawn();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the entity that represents the bubble
+    pub fn entity(&self, id: BubbleId) -> Option<Entity> {
+        self.registry.entity(id)
+    }
+
+    /// Returns the bubble that `entity` represents
+    pub fn bubble(&self, entity: Entity) -> Option<BubbleId> {
+        self.registry.bubble(entity)
+    }
+
+    /// Looks up the current position of each bubble
+    pub fn positions<F: ReadOnlyWorldQuery>(
+        &self,
+        transforms: &Query<&Transform, F>,
+    ) -> HashMap<BubbleId, Vec2> {
+        self.registry
+            .ids()
+            .filter_map(|(id, entity)| {
+                Some((id, transforms.get(entity).ok()?.translation.truncate()))
+            })
             .collect()
     }
-}
 
-const SPAWN_DIST: f32 = 100.;
-const BUBBLE_SPAWN_FORCE_DIST: f32 = 50.;
-const BUBBLE_SPAWN_FORCE: f32 = 0.00001;
+    /// Lists all divergences between the graph, the entity registry and the bubbles in the world
+    ///
+    /// Bubbles spawned during this frame are reported as missing until commands are applied.
+    pub fn check_consistency<'a>(
+        &self,
+        world: impl Iterator<Item = (Entity, &'a GraphBubble)>,
+    ) -> Vec<Inconsistency> {
+        let mut ret = vec![];
+        let in_graph = self.graph.ids().into_iter().collect::<HashSet<_>>();
+        let in_world = world.map(|(e, id)| (e, id.0)).collect::<HashMap<_, _>>();
+
+        for id in &in_graph {
+            if self.registry.entity(*id).is_none() {
+                ret.push(Inconsistency::Unregistered(*id));
+            }
+        }
+        for (id, entity) in self.registry.ids() {
+            if !in_graph.contains(&id) {
+                ret.push(Inconsistency::NotInGraph(id));
+            }
+            if in_world.get(&entity) != Some(&id) {
+                ret.push(Inconsistency::MissingEntity(id, entity));
+            }
+        }
+        for (entity, id) in &in_world {
+            if self.registry.entity(*id) != Some(*entity) {
+                ret.push(Inconsistency::UnknownEntity(*entity, *id));
+            }
+        }
+        ret.sort();
+        ret
+    }
+
+    /// Returns the bubbles that `idx` is connected to
+    pub fn neighbors(&self, idx: BubbleId) -> Result<Vec<BubbleId>, BubbleGraphError> {
+        self.graph.neighbors(idx)
+    }
+
+    /// Number of connections of the bubble in either direction, 0 if it does not exist
+    pub fn degree(&self, id: BubbleId) -> usize {
+        self.graph.degree(id)
+    }
+
+    /// Returns the bubbles connected to the bubble, i.e., its children
+    pub fn children(&self, id: BubbleId) -> Result<Vec<BubbleId>, BubbleGraphError> {
+        self.graph.children(id)
+    }
 
-fn below(origin: Vec2, dist: f32) -> Vec2 {
-    origin + Vec2::NEG_Y * dist
+    /// Returns the bubble and its descendants in breadth-first order, the bubble first
+    ///
+    /// With a `depth`, only that many levels are included, so a depth of 1 is just the bubble.
+    pub fn subtree(
+        &self,
+        root: BubbleId,
+        depth: Option<usize>,
+    ) -> Result<Vec<BubbleId>, BubbleGraphError> {
+        let mut ret = vec![root];
+        let mut seen = HashSet::from([root]);
+        let mut level = vec![root];
+        let mut levels = 1;
+        while !level.is_empty() && depth.is_none_or(|depth| levels < depth) {
+            let mut next = vec![];
+            for id in level {
+                for child in self.graph.children(id)? {
+                    // The graph may contain cycles
+                    if seen.insert(child) {
+                        next.push(child);
+                    }
+                }
+            }
+            ret.extend(&next);
+            level = next;
+            levels += 1;
+        }
+        Ok(ret)
+    }
+
+    /// Copies the bubble and its descendants, see [`Bubbles::subtree`]
+    ///
+    /// `lookup` returns the content, color and position of a bubble.
+    pub fn copy_subtree(
+        &self,
+        root: BubbleId,
+        depth: Option<usize>,
+        lookup: impl Fn(BubbleId) -> Option<(String, Color, Vec2)>,
+    ) -> Result<SubtreeCopy, BubbleGraphError> {
+        let ids = self.subtree(root, depth)?;
+        let mut bubbles = Vec::with_capacity(ids.len());
+        let mut root_pos = None;
+        for id in &ids {
+            let (content, color, pos) = lookup(*id).ok_or(BubbleGraphError::NoPosition(*id))?;
+            let root_pos = *root_pos.get_or_insert(pos);
+            bubbles.push(CopiedBubble {
+                content,
+                color,
+                offset: pos - root_pos,
+            });
+        }
+
+        let index = |id| ids.iter().position(|other| *other == id);
+        let mut edges = vec![];
+        for edge in self.graph.edge_ids() {
+            let (from, to) = self.graph.edge_endpoints(edge)?;
+            if let (Some(from), Some(to)) = (index(from), index(to)) {
+                edges.push((from, to, self.graph.edge_kind(edge)?));
+            }
+        }
+        Ok(SubtreeCopy { bubbles, edges })
+    }
+
+    /// Spawns a copy of a subtree with its root as a new child of `parent`, and returns the root
+    pub fn paste(
+        &mut self,
+        parent: BubbleId,
+        copy: &SubtreeCopy,
+        render_graph: &mut BubbleBundleBuilder,
+        rng: &mut impl Rng,
+    ) -> Result<BubbleId, BubbleGraphError> {
+        let root = self.spawn_child(parent, render_graph, rng)?;
+        let root_pos = render_graph
+            .position(root)
+            .ok_or(BubbleGraphError::NoPosition(root))?;
+        let mut ids = vec![root];
+        for bubble in &copy.bubbles[1..] {
+            ids.push(self.spawn_orphan(root_pos + bubble.offset, render_graph));
+        }
+        for (id, bubble) in ids.iter().zip(&copy.bubbles) {
+            if let Some(entity) = self.entity(*id) {
+                render_graph.set_style(entity, &bubble.content, bubble.color);
+            }
+        }
+        for (from, to, kind) in &copy.edges {
+            let edge = self.connect(ids[*from], ids[*to], render_graph)?;
+            if *kind != EdgeKind::default() {
+                self.set_edge_kind(edge, *kind, render_graph.commands())?;
+            }
+        }
+        Ok(root)
+    }
+
+    /// Returns the connected component the bubble belongs to, or `None` if it does not exist
+    pub fn subgraph(&self, id: BubbleId) -> Option<SubgraphId> {
+        self.subgraphs.get(&id).copied()
+    }
+
+    /// Returns the IDs of all bubbles in the graph
+    pub fn ids(&self) -> Vec<BubbleId> {
+        self.graph.ids()
+    }
+
+    /// Returns all connections as `(from, to)`, e.g., `(child, parent)`
+    pub fn edges(&self) -> Vec<(BubbleId, BubbleId)> {
+        self.graph.edges()
+    }
+
+    pub fn edge_ids(&self) -> Vec<EdgeId> {
+        self.graph.edge_ids()
+    }
+}
+
+/// Warns about divergences between the bubble graph and the ECS world, see
+/// [`Bubbles::check_consistency`]
+///
+/// Each distinct set of divergences is reported once.
+pub fn check_bubble_consistency(
+    bubbles: Res<Bubbles>,
+    q: Query<(Entity, &GraphBubble)>,
+    mut reported: Local<Vec<Inconsistency>>,
+) {
+    let found = bubbles.check_consistency(q.iter());
+    if found != *reported {
+        for inconsistency in &found {
+            warn!("Bubble registry is inconsistent: {inconsistency}");
+        }
+        *reported = found;
+    }
 }
+
+const SPAWN_DIST: f32 = 100.;
@@ -0,0 +1,73 @@
+use std::{
+    f32::consts::{FRAC_PI_2, TAU},
+    iter,
+};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+/// Bubbles further than this many spawn distances from the parent do not affect placement
+const CROWD_RADIUS_MULT: f32 = 2.;
+/// Maximum random deviation from the middle of the free gap, in radians
+const ANGLE_JITTER: f32 = 0.1;
+
+/// Picks a position for a new child of the bubble at `parent_pos`
+///
+/// The child is placed `dist` away from the parent, in the middle of the largest free angular gap
+/// around it. The gap is bounded by the parent's neighbors (`grandparent` included), and any other
+/// bubbles in the parent's vicinity. With no neighbors, the child goes below the parent.
+///
+/// # Arguments
+///
+/// * `neighbors` - Positions of the bubbles connected to the parent, including the grandparent
+/// * `others` - Positions of all other bubbles, only the ones close to the parent are considered
+pub(crate) fn child_position(
+    parent_pos: Vec2,
+    neighbors: impl IntoIterator<Item = Vec2>,
+    others: impl IntoIterator<Item = Vec2>,
+    dist: f32,
+    rng: &mut impl Rng,
+) -> Vec2 {
+    let crowd_radius = CROWD_RADIUS_MULT * dist;
+    let mut occupied = neighbors
+        .into_iter()
+        .chain(
+            others
+                .into_iter()
+                .filter(|pos| pos.distance(parent_pos) <= crowd_radius),
+        )
+        .map(|pos| pos - parent_pos)
+        // Bubbles exactly on top of the parent have no direction
+        .filter(|diff| *diff != Vec2::ZERO)
+        .map(|diff| diff.y.atan2(diff.x))
+        .collect::<Vec<_>>();
+
+    let jitter = rng.gen_range(-ANGLE_JITTER..=ANGLE_JITTER);
+    let angle = match largest_gap(&mut occupied) {
+        Some((start, width)) => start + width / 2.,
+        // Straight down
+        None => -FRAC_PI_2,
+    } + jitter;
+
+    parent_pos + Vec2::from_angle(angle) * dist
+}
+
+/// Returns the start angle and the width of the largest gap between `angles`, going
+/// counter-clockwise, or `None` if there are no angles
+fn largest_gap(angles: &mut [f32]) -> Option<(f32, f32)> {
+    angles.sort_by(f32::total_cmp);
+    let first = *angles.first()?;
+
+    // The gap after the last angle wraps around to the first one
+    let ends = angles
+        .iter()
+        .skip(1)
+        .copied()
+        .chain(iter::once(first + TAU));
+    angles
+        .iter()
+        .copied()
+        .zip(ends)
+        .map(|(start, end)| (start, end - start))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
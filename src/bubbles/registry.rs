@@ -0,0 +1,80 @@
+use std::fmt;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{BubbleId, EdgeId};
+
+/// Maps bubbles and connections to the entities that represent them in the ECS world, and back
+#[derive(Default)]
+pub(crate) struct EntityRegistry {
+    by_id: HashMap<BubbleId, Entity>,
+    by_entity: HashMap<Entity, BubbleId>,
+    /// Connection entities by edge
+    connections: HashMap<EdgeId, Entity>,
+}
+
+impl EntityRegistry {
+    pub fn insert(&mut self, id: BubbleId, entity: Entity) {
+        self.by_id.insert(id, entity);
+        self.by_entity.insert(entity, id);
+    }
+
+    /// Returns the entity of the removed bubble
+    pub fn remove(&mut self, id: BubbleId) -> Option<Entity> {
+        let entity = self.by_id.remove(&id)?;
+        self.by_entity.remove(&entity);
+        Some(entity)
+    }
+
+    pub fn entity(&self, id: BubbleId) -> Option<Entity> {
+        self.by_id.get(&id).copied()
+    }
+
+    pub fn bubble(&self, entity: Entity) -> Option<BubbleId> {
+        self.by_entity.get(&entity).copied()
+    }
+
+    pub fn insert_connection(&mut self, edge: EdgeId, entity: Entity) {
+        self.connections.insert(edge, entity);
+    }
+
+    pub fn remove_connection(&mut self, edge: EdgeId) -> Option<Entity> {
+        self.connections.remove(&edge)
+    }
+
+    pub fn connection(&self, edge: EdgeId) -> Option<Entity> {
+        self.connections.get(&edge).copied()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = (BubbleId, Entity)> + '_ {
+        self.by_id.iter().map(|(id, entity)| (*id, *entity))
+    }
+}
+
+/// A divergence between the bubble graph, the registry and the ECS world
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Inconsistency {
+    /// The bubble is in the graph but has no registered entity
+    Unregistered(BubbleId),
+    /// The bubble has a registered entity that does not exist in the world
+    MissingEntity(BubbleId, Entity),
+    /// An entity in the world claims to be a bubble that is not registered to it
+    UnknownEntity(Entity, BubbleId),
+    /// The bubble is registered but not in the graph
+    NotInGraph(BubbleId),
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inconsistency::Unregistered(id) => write!(f, "bubble {id} has no entity"),
+            Inconsistency::MissingEntity(id, e) => {
+                write!(f, "bubble {id} is registered to {e:?} which does not exist")
+            }
+            Inconsistency::UnknownEntity(e, id) => {
+                write!(f, "{e:?} is bubble {id} but not registered as such")
+            }
+            Inconsistency::NotInGraph(id) => write!(f, "bubble {id} is not in the graph"),
+        }
+    }
+}
@@ -1,30 +1,156 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::cursor_control::{CursorControl, InputMode};
+use crate::{
+    bubbles::{apply_bubble_commands, BubbleCommand, BubbleEvent, BubbleId},
+    cursor_control::{forget_removed, BulkOp, Change, CursorControl, InputMode},
+    input::{Direction, NavTargets},
+};
+
+/// Spawns the [`MainCamera`] and moves it according to [`ControlEvent`]s
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ControlEvent>()
+            .add_event::<BubbleCommand>()
+            .add_event::<BubbleEvent>()
+            .init_resource::<CursorControl>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                Update,
+                (
+                    handle_view_event,
+                    forget_removed.after(apply_bubble_commands),
+                ),
+            );
+    }
+}
 
 #[derive(Component)]
 pub struct MainCamera;
 
-#[derive(Event, Debug, Clone)]
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), MainCamera));
+}
+
+#[derive(Event, Debug, Clone, Serialize, Deserialize)]
 pub enum ControlEvent {
     Pan(Vec2),
     ZoomIn(f32),
+    /// Scale the view by `factor` around `anchor`, given in logical pixels from the top left of
+    /// the window, so that what is under the anchor stays there
+    ZoomAt {
+        factor: f32,
+        anchor: Vec2,
+    },
     ChangeMode(InputMode),
+    /// Select only the bubble
+    Select(BubbleId),
+    /// Add the bubble to the selection or remove it from there
+    ToggleSelect(BubbleId),
+    /// Add the bubbles to the selection
+    ExtendSelection(Vec<BubbleId>),
+    ClearSelection,
+    /// Move `times` bubbles in `dir`, starting from the focused bubble: travel mode selects the
+    /// bubble reached, visual mode adds the bubbles passed to the selection and link mode moves
+    /// the end of the link instead
+    Navigate {
+        dir: Direction,
+        times: usize,
+    },
+    /// Apply the operation to all selected bubbles
+    Bulk(BulkOp),
+    /// Edit the graph around the focused bubble
+    Change(Change),
+    /// Copy the focused bubble and its descendants, only `depth` levels deep if given
+    Yank {
+        depth: Option<usize>,
+    },
+    /// Open the context menu for what is at the point, in logical pixels from the top left of
+    /// the window
+    OpenContextMenu(Vec2),
+    /// Apply a graph edit picked with the mouse, e.g., from the context menu, so that macros
+    /// record it like the edits made with keys
+    Edit(BubbleCommand),
 }
 
 const MIN_SCALE: f32 = 0.2;
 
 pub fn handle_view_event(
     mut view_moves: EventReader<ControlEvent>,
-    mut q: Query<&mut OrthographicProjection, With<MainCamera>>,
+    mut q: Query<(&mut OrthographicProjection, &Camera), With<MainCamera>>,
     mut control: ResMut<CursorControl>,
+    mut bubble_commands: EventWriter<BubbleCommand>,
+    targets: NavTargets,
 ) {
     for motion in view_moves.iter() {
-        let mut projection = q.single_mut();
+        let (mut projection, camera) = q.single_mut();
         match motion {
             ControlEvent::Pan(xy) => handle_pan(&mut projection, xy),
             ControlEvent::ZoomIn(amount) => handle_zoom_in(&mut projection, *amount),
+            ControlEvent::ZoomAt { factor, anchor } => {
+                handle_zoom_at(&mut projection, camera, *factor, *anchor)
+            }
             ControlEvent::ChangeMode(nmode) => handle_change_mode(&mut control, nmode.clone()),
+            ControlEvent::Select(id) => control.select(*id),
+            ControlEvent::ToggleSelect(id) => control.toggle(*id),
+            ControlEvent::ExtendSelection(ids) => control.extend(ids),
+            ControlEvent::ClearSelection => control.clear_selection(),
+            ControlEvent::Navigate { dir, times } => {
+                handle_navigate(&mut control, &targets, *dir, *times)
+            }
+            ControlEvent::Bulk(op) => handle_bulk(&mut control, op, &mut bubble_commands),
+            ControlEvent::Edit(command) => bubble_commands.send(command.clone()),
+            // Applied by `input::apply_changes`, which keeps the yanked bubbles
+            ControlEvent::Change(_) | ControlEvent::Yank { .. } => {}
+            // Applied by `input::handle_context_menu`
+            ControlEvent::OpenContextMenu(_) => {}
+        }
+    }
+}
+
+fn handle_bulk(
+    control: &mut CursorControl,
+    op: &BulkOp,
+    bubble_commands: &mut EventWriter<BubbleCommand>,
+) {
+    bubble_commands.send_batch(control.bulk_commands(op));
+    if let BulkOp::Delete = op {
+        control.clear_selection();
+    }
+}
+
+fn handle_navigate(
+    control: &mut CursorControl,
+    targets: &NavTargets,
+    dir: Direction,
+    times: usize,
+) {
+    let path = |from| {
+        std::iter::successors(Some(from), |at| targets.in_direction(*at, dir.vec()))
+            .skip(1)
+            .take(times)
+            .collect::<Vec<_>>()
+    };
+    match control.input_mode {
+        InputMode::Link { from, to } => {
+            if let Some(to) = path(to).last() {
+                control.input_mode = InputMode::Link { from, to: *to };
+            }
+        }
+        InputMode::Visual => {
+            if let Some(focused) = control.focused {
+                control.extend(&path(focused));
+            }
+        }
+        InputMode::Travel | InputMode::Edit(_) => {
+            if let Some(target) = control
+                .focused
+                .and_then(|focused| path(focused).last().copied())
+            {
+                control.select(target);
+            }
         }
     }
 }
@@ -37,6 +163,23 @@ fn handle_zoom_in(projection: &mut OrthographicProjection, amount: f32) {
     projection.scale = (projection.scale - amount).max(MIN_SCALE);
 }
 
+fn handle_zoom_at(
+    projection: &mut OrthographicProjection,
+    camera: &Camera,
+    factor: f32,
+    anchor: Vec2,
+) {
+    let old_scale = projection.scale;
+    projection.scale = (projection.scale / factor).max(MIN_SCALE);
+    let Some(size) = camera.logical_viewport_size() else {
+        return;
+    };
+    // The viewport origin is a fraction of the viewport, with y pointing up
+    let anchor = Vec2::new(anchor.x, size.y - anchor.y) / size;
+    projection.viewport_origin =
+        anchor - (anchor - projection.viewport_origin) * old_scale / projection.scale;
+}
+
 fn handle_pan(projection: &mut OrthographicProjection, xy: &Vec2) {
     let a = &projection.area;
     let pan = Vec2::new(-xy.x / a.width(), xy.y / a.height()) * projection.scale;
@@ -1,20 +1,155 @@
-use crate::bubbles::BubbleId;
+use std::collections::BTreeSet;
+
+use crate::bubbles::{BubbleCommand, BubbleEvent, BubbleId};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(Resource, Default)]
 pub struct CursorControl {
     pub input_mode: InputMode,
-    /// Selected bubble
-    pub selected: Option<BubbleId>,
+    /// Selected bubbles
+    pub selected: BTreeSet<BubbleId>,
+    /// The most recently selected bubble, which single-bubble actions apply to
+    pub focused: Option<BubbleId>,
+}
+
+impl CursorControl {
+    /// Replaces the selection with `id`
+    pub fn select(&mut self, id: BubbleId) {
+        self.selected.clear();
+        self.selected.insert(id);
+        self.focused = Some(id);
+    }
+
+    /// Adds `id` to the selection, or removes it if it was already selected
+    pub fn toggle(&mut self, id: BubbleId) {
+        if self.selected.remove(&id) {
+            if self.focused == Some(id) {
+                self.focused = self.selected.iter().next_back().copied();
+            }
+        } else {
+            self.selected.insert(id);
+            self.focused = Some(id);
+        }
+    }
+
+    /// Adds `ids` to the selection, focusing the last one
+    pub fn extend(&mut self, ids: &[BubbleId]) {
+        self.selected.extend(ids);
+        if let Some(last) = ids.last() {
+            self.focused = Some(*last);
+        }
+    }
+
+    /// Removes `ids` from the selection, e.g., because they were deleted
+    ///
+    /// Edit and link mode return to travel mode if they were working on one of them.
+    pub fn forget(&mut self, ids: &[BubbleId]) {
+        for id in ids {
+            self.selected.remove(id);
+        }
+        if self.focused.is_some_and(|focused| ids.contains(&focused)) {
+            self.focused = self.selected.iter().next_back().copied();
+        }
+        let uses_forgotten = match self.input_mode {
+            InputMode::Edit(id) => ids.contains(&id),
+            InputMode::Link { from, to } => ids.contains(&from) || ids.contains(&to),
+            InputMode::Travel | InputMode::Visual => false,
+        };
+        if uses_forgotten {
+            self.input_mode = InputMode::Travel;
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected.clear();
+        self.focused = None;
+    }
+
+    /// Returns the commands that apply `op` to every selected bubble
+    pub fn bulk_commands(&self, op: &BulkOp) -> Vec<BubbleCommand> {
+        let ids = self.selected.iter().copied();
+        match op {
+            BulkOp::Delete => ids.map(BubbleCommand::Remove).collect(),
+            BulkOp::ConnectToFocused => {
+                let Some(target) = self.focused else {
+                    return vec![];
+                };
+                ids.filter(|id| *id != target)
+                    .map(|id| BubbleCommand::Connect(id, target))
+                    .collect()
+            }
+            BulkOp::Group => vec![BubbleCommand::Group(ids.collect())],
+            BulkOp::SetColor(color) => ids.map(|id| BubbleCommand::SetColor(id, *color)).collect(),
+        }
+    }
+}
+
+/// An operation on all selected bubbles, see [`CursorControl::bulk_commands`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BulkOp {
+    Delete,
+    /// Connect every selected bubble to the focused one as its child
+    ConnectToFocused,
+    /// Connect the selected bubbles to a new bubble in their middle
+    Group,
+    SetColor(Color),
+}
+
+/// An edit of the graph around the focused bubble in travel mode, see
+/// [`apply_changes`](crate::input::apply_changes)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Change {
+    /// Delete the focused bubble
+    DeleteBubble,
+    /// Delete the focused bubble and its descendants, only `depth` levels deep if given
+    DeleteSubtree { depth: Option<usize> },
+    /// Paste the yanked subtree as a child of the focused bubble, `count` times
+    Paste { count: usize },
+    /// Spawn `count` bubbles as children of the focused bubble's parent
+    NewSibling { count: usize },
+    /// Spawn `count` children of the focused bubble
+    NewChild { count: usize },
+}
+
+impl Change {
+    /// The same change with another count or depth, for repeating it with a count
+    pub fn with_count(self, count: usize) -> Self {
+        match self {
+            Change::DeleteBubble => Change::DeleteBubble,
+            Change::DeleteSubtree { .. } => Change::DeleteSubtree { depth: Some(count) },
+            Change::Paste { .. } => Change::Paste { count },
+            Change::NewSibling { .. } => Change::NewSibling { count },
+            Change::NewChild { .. } => Change::NewChild { count },
+        }
+    }
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub enum InputMode {
     /// Pan view & select bubbles
     #[default]
     Travel,
     /// Edit the focused bubble
     Edit(BubbleId),
+    /// Pick a bubble to connect to `from` as its child, `to` is the current pick
+    Link { from: BubbleId, to: BubbleId },
+    /// Extend the selection with the arrow keys and apply bulk operations to it
+    Visual,
+}
+
+/// Forgets bubbles as soon as they are removed, whatever removed them
+pub fn forget_removed(mut events: EventReader<BubbleEvent>, mut control: ResMut<CursorControl>) {
+    let removed = events
+        .iter()
+        .filter_map(|ev| match ev {
+            BubbleEvent::Applied(BubbleCommand::Remove(id)) => Some(*id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !removed.is_empty() {
+        control.forget(&removed);
+    }
 }
 
 impl InputMode {
@@ -22,6 +157,36 @@ impl InputMode {
         match self {
             InputMode::Travel => "travel",
             InputMode::Edit(_) => "edit",
+            InputMode::Link { .. } => "link",
+            InputMode::Visual => "visual",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bubbles::apply_bubble_commands, headless_app};
+
+    #[test]
+    fn removed_bubbles_are_forgotten() {
+        let mut app = headless_app();
+        app.add_systems(Update, forget_removed.after(apply_bubble_commands))
+            .init_resource::<CursorControl>();
+        app.world.send_event(BubbleCommand::SpawnOrphan(Vec2::ZERO));
+        app.world
+            .send_event(BubbleCommand::SpawnOrphan(Vec2::X * 200.));
+        app.update();
+
+        let mut control = app.world.resource_mut::<CursorControl>();
+        control.extend(&[0, 1]);
+        control.input_mode = InputMode::Edit(1);
+        app.world.send_event(BubbleCommand::Remove(1));
+        app.update();
+
+        let control = app.world.resource::<CursorControl>();
+        assert_eq!(control.selected, BTreeSet::from([0]));
+        assert_eq!(control.focused, Some(0));
+        assert!(matches!(control.input_mode, InputMode::Travel));
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    physics::{spring_force, BubblePhysics, GlobalPhysics},
+    BubbleConnection, GraphBubble,
+};
+
+const VELOCITY_COLOR: Color = Color::YELLOW;
+const CENTER_COLOR: Color = Color::GREEN;
+const SUBGRAPH_COLOR: Color = Color::TEAL;
+const REPEL_COLOR: Color = Color::ORANGE_RED;
+const LINK_COLOR: Color = Color::CYAN;
+const TOTAL_COLOR: Color = Color::WHITE;
+
+/// Settings for the physics debug overlay
+#[derive(Resource)]
+pub struct PhysicsDebug {
+    pub enabled: bool,
+    pub show_velocity: bool,
+    /// Show the net acceleration of each bubble, broken down by source
+    pub show_forces: bool,
+    /// Color connections by spring tension
+    pub show_springs: bool,
+    /// Length of a drawn force vector per unit of acceleration
+    pub force_scale: f32,
+    /// Length of a drawn velocity vector per unit of velocity
+    pub velocity_scale: f32,
+}
+
+impl Default for PhysicsDebug {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            show_velocity: true,
+            show_forces: true,
+            show_springs: true,
+            force_scale: 0.1,
+            velocity_scale: 0.5,
+        }
+    }
+}
+
+/// Recent history of physics totals, for plotting
+#[derive(Resource)]
+pub struct PhysicsStats {
+    /// Total kinetic energy of all bubbles per frame, oldest first
+    pub kinetic_energy: VecDeque<f32>,
+    /// Highest bubble speed per frame, oldest first
+    pub max_speed: VecDeque<f32>,
+    max_len: usize,
+}
+
+impl Default for PhysicsStats {
+    fn default() -> Self {
+        Self::with_capacity(256)
+    }
+}
+
+impl PhysicsStats {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            kinetic_energy: VecDeque::with_capacity(capacity),
+            max_speed: VecDeque::with_capacity(capacity),
+            max_len: capacity,
+        }
+    }
+
+    fn push(&mut self, kinetic_energy: f32, max_speed: f32) {
+        if self.kinetic_energy.len() == self.max_len {
+            self.kinetic_energy.pop_front();
+            self.max_speed.pop_front();
+        }
+        self.kinetic_energy.push_back(kinetic_energy);
+        self.max_speed.push_back(max_speed);
+    }
+}
+
+pub fn record_physics_stats(q: Query<&BubblePhysics>, mut stats: ResMut<PhysicsStats>) {
+    let kinetic_energy = q.iter().map(BubblePhysics::kinetic_energy).sum();
+    let max_speed = q.iter().map(|b| b.vel.length()).fold(0., f32::max);
+    stats.push(kinetic_energy, max_speed);
+}
+
+pub fn draw_physics_gizmos(
+    mut gizmos: Gizmos,
+    debug: Res<PhysicsDebug>,
+    gphysics: Res<GlobalPhysics>,
+    bubbles: Query<(&BubblePhysics, &Transform, &GraphBubble)>,
+    links: Query<&BubbleConnection>,
+) {
+    if !debug.enabled {
+        return;
+    }
+
+    if debug.show_velocity || debug.show_forces {
+        for (phys, tfm, _) in bubbles.iter() {
+            draw_bubble_vectors(&mut gizmos, &debug, phys, tfm.translation.truncate());
+        }
+    }
+
+    if debug.show_springs {
+        let pos_by_id = bubbles
+            .iter()
+            .map(|(_, tfm, id)| (id.0, tfm.translation.truncate()))
+            .collect::<HashMap<_, _>>();
+        for conn in links.iter() {
+            let (Some(from), Some(to)) = (pos_by_id.get(&conn.0), pos_by_id.get(&conn.1)) else {
+                continue;
+            };
+            let tension = spring_force(gphysics.flink, gphysics.link_length, *to - *from).length();
+            gizmos.line_2d(*from, *to, tension_color(tension * debug.force_scale));
+        }
+    }
+}
+
+fn draw_bubble_vectors(gizmos: &mut Gizmos, debug: &PhysicsDebug, phys: &BubblePhysics, pos: Vec2) {
+    if debug.show_velocity {
+        gizmos.ray_2d(pos, phys.vel * debug.velocity_scale, VELOCITY_COLOR);
+    }
+    if debug.show_forces {
+        let f = &phys.forces;
+        for (a, color) in [
+            (f.center, CENTER_COLOR),
+            (f.subgraph, SUBGRAPH_COLOR),
+            (f.repel, REPEL_COLOR),
+            (f.link, LINK_COLOR),
+            (f.total(), TOTAL_COLOR),
+        ] {
+            gizmos.ray_2d(pos, a * debug.force_scale, color);
+        }
+    }
+}
+
+/// Green for a slack spring, red when the scaled tension reaches 100 px
+fn tension_color(scaled_tension: f32) -> Color {
+    let t = (scaled_tension / 100.).clamp(0., 1.);
+    Color::rgb(t, 1. - t, 0.)
+}
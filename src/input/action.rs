@@ -0,0 +1,298 @@
+//! What keys do, independent of which keys are bound to it
+//!
+//! The [`Keymap`](super::Keymap) turns key presses into [`ActionEvent`]s, and [`handle_actions`]
+//! carries them out according to the current [`InputMode`].
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    edit_mode, link_mode, travel_mode, visual_mode, CommandPalette, Macros, Register, PALETTE,
+};
+use crate::{
+    bubbles::BubbleCommand,
+    camera::ControlEvent,
+    cursor_control::{CursorControl, InputMode},
+};
+
+/// An [`Action`] triggered by keys or from the command palette
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ActionEvent {
+    pub action: Action,
+    /// Number typed before the keys, e.g., 3 in `3j`
+    pub count: Option<usize>,
+}
+
+impl From<Action> for ActionEvent {
+    fn from(action: Action) -> Self {
+        Self {
+            action,
+            count: None,
+        }
+    }
+}
+
+/// Something a key binding does
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    /// Move the view while the key is held
+    Pan(Direction),
+    /// Edit the focused bubble
+    Edit,
+    /// Start picking a bubble to link to the focused one as its child
+    Link,
+    /// Start extending the selection from the focused bubble
+    Visual,
+    /// Return to travel mode
+    Travel,
+    /// Pick the closest bubble in the direction, to focus, as link target or into the selection
+    ///
+    /// A count moves that many bubbles in travel mode.
+    Navigate(Direction),
+    /// Connect the link target to the bubble the link started from
+    ConfirmLink,
+    /// Delete the selected bubbles
+    Delete,
+    /// Connect the selected bubbles to the focused one
+    ConnectToFocused,
+    /// Group the selected bubbles under a new bubble
+    Group,
+    /// Change the color of the selected bubbles to the given entry of [`PALETTE`]
+    Recolor(usize),
+    ZoomIn,
+    ZoomOut,
+    /// Spawn a child of the focused bubble, or as many as the count
+    AddChild,
+    /// Spawn a child of the focused bubble's parent, or as many as the count
+    NewSibling,
+    /// Delete the focused bubble
+    DeleteFocused,
+    /// Delete the focused bubble and its descendants, as many levels as the count
+    DeleteSubtree,
+    /// Copy the focused bubble and its descendants, as many levels as the count
+    YankSubtree,
+    /// Paste the yanked bubbles as a child of the focused bubble, as many times as the count
+    Paste,
+    /// Repeat the last change, with the new count if one is given
+    RepeatChange,
+    ClearSelection,
+    /// Move all bubbles to where the physics would settle them
+    SettleLayout,
+    /// Open or close the command palette
+    CommandPalette,
+    /// Start recording a macro, or stop and save it
+    RecordMacro,
+    /// Play the selected macro on each selected bubble, as many times as the count
+    PlayMacro,
+}
+
+impl Action {
+    /// Names of the modes in which the action does something, see [`InputMode::as_str`]
+    pub fn modes(&self) -> &'static [&'static str] {
+        match self {
+            Action::Pan(_)
+            | Action::Edit
+            | Action::Link
+            | Action::Visual
+            | Action::ZoomIn
+            | Action::ZoomOut
+            | Action::AddChild
+            | Action::NewSibling
+            | Action::DeleteFocused
+            | Action::DeleteSubtree
+            | Action::YankSubtree
+            | Action::Paste
+            | Action::RepeatChange
+            | Action::ClearSelection
+            | Action::SettleLayout
+            | Action::RecordMacro
+            | Action::PlayMacro => &["travel"],
+            Action::Travel => &["edit", "link", "visual"],
+            Action::Navigate(_) => &["travel", "link", "visual"],
+            Action::ConfirmLink => &["link"],
+            Action::Delete | Action::ConnectToFocused | Action::Group | Action::Recolor(_) => {
+                &["visual"]
+            }
+            Action::CommandPalette => &["travel", "edit", "link", "visual"],
+        }
+    }
+
+    /// Every action, e.g., for listing them in the command palette
+    pub fn all() -> Vec<Action> {
+        let mut all = vec![
+            Action::CommandPalette,
+            Action::Travel,
+            Action::Edit,
+            Action::Link,
+            Action::Visual,
+            Action::ZoomIn,
+            Action::ZoomOut,
+            Action::AddChild,
+            Action::NewSibling,
+            Action::DeleteFocused,
+            Action::DeleteSubtree,
+            Action::YankSubtree,
+            Action::Paste,
+            Action::RepeatChange,
+            Action::ConfirmLink,
+            Action::Delete,
+            Action::ConnectToFocused,
+            Action::Group,
+            Action::ClearSelection,
+            Action::SettleLayout,
+            Action::RecordMacro,
+            Action::PlayMacro,
+        ];
+        all.extend((0..PALETTE.len()).map(Action::Recolor));
+        all.extend(Direction::ALL.map(Action::Navigate));
+        all.extend(Direction::ALL.map(Action::Pan));
+        all
+    }
+
+    /// True if the action lasts for as long as its key is held, rather than happening once
+    pub fn is_held(&self) -> bool {
+        matches!(self, Action::Pan(_))
+    }
+
+    /// What the action is about, for grouping actions in the UI
+    pub fn category(&self) -> &'static str {
+        match self {
+            Action::Pan(_) | Action::ZoomIn | Action::ZoomOut => "View",
+            Action::Edit | Action::Link | Action::Visual | Action::Travel => "Mode",
+            Action::Navigate(_) | Action::ClearSelection => "Selection",
+            Action::ConfirmLink
+            | Action::Delete
+            | Action::ConnectToFocused
+            | Action::Group
+            | Action::Recolor(_)
+            | Action::AddChild
+            | Action::NewSibling
+            | Action::DeleteFocused
+            | Action::DeleteSubtree
+            | Action::YankSubtree
+            | Action::Paste
+            | Action::RepeatChange => "Graph",
+            Action::SettleLayout => "Layout",
+            Action::RecordMacro | Action::PlayMacro => "Macro",
+            Action::CommandPalette => "General",
+        }
+    }
+
+    /// Short description for the UI
+    pub fn label(&self) -> String {
+        match self {
+            Action::Pan(dir) => format!("Pan {}", dir.as_str()),
+            Action::Edit => "Edit focused bubble".to_string(),
+            Action::Link => "Link to focused bubble".to_string(),
+            Action::Visual => "Visual mode".to_string(),
+            Action::Travel => "Travel mode".to_string(),
+            Action::Navigate(dir) => format!("Go {}", dir.as_str()),
+            Action::ConfirmLink => "Connect link target".to_string(),
+            Action::Delete => "Delete selection".to_string(),
+            Action::ConnectToFocused => "Connect selection to focused".to_string(),
+            Action::Group => "Group selection".to_string(),
+            Action::Recolor(idx) => format!("Recolor selection with color {}", idx + 1),
+            Action::ZoomIn => "Zoom in".to_string(),
+            Action::ZoomOut => "Zoom out".to_string(),
+            Action::AddChild => "Add child to focused bubble".to_string(),
+            Action::NewSibling => "Add sibling to focused bubble".to_string(),
+            Action::DeleteFocused => "Delete focused bubble".to_string(),
+            Action::DeleteSubtree => "Delete focused subtree".to_string(),
+            Action::YankSubtree => "Yank focused subtree".to_string(),
+            Action::Paste => "Paste as child of focused bubble".to_string(),
+            Action::RepeatChange => "Repeat last change".to_string(),
+            Action::ClearSelection => "Clear selection".to_string(),
+            Action::SettleLayout => "Settle layout".to_string(),
+            Action::CommandPalette => "Command palette".to_string(),
+            Action::RecordMacro => "Start or stop recording a macro".to_string(),
+            Action::PlayMacro => "Play macro on selection".to_string(),
+        }
+    }
+
+    /// Describes why the action cannot be bound, if it cannot
+    pub fn check(&self) -> Option<String> {
+        match self {
+            Action::Recolor(idx) if *idx >= PALETTE.len() => Some(format!(
+                "there is no color {idx}, the palette has {} colors",
+                PALETTE.len()
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// An arrow key direction
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+
+    /// Unit vector in world coordinates, i.e., up is positive y
+    pub fn vec(self) -> Vec2 {
+        match self {
+            Direction::Left => Vec2::NEG_X,
+            Direction::Right => Vec2::X,
+            Direction::Up => Vec2::Y,
+            Direction::Down => Vec2::NEG_Y,
+        }
+    }
+}
+
+/// Carries out the [`Action`]s sent this frame in the current input mode
+pub fn handle_actions(
+    mut actions: EventReader<ActionEvent>,
+    (control, register): (Res<CursorControl>, Res<Register>),
+    mut control_events: EventWriter<ControlEvent>,
+    mut bubble_commands: EventWriter<BubbleCommand>,
+    (mut palette, mut macros): (ResMut<CommandPalette>, ResMut<Macros>),
+) {
+    for ev in actions.iter().copied() {
+        let action = ev.action;
+        let result = match action {
+            Action::CommandPalette => {
+                palette.toggle();
+                continue;
+            }
+            Action::RecordMacro => macros.toggle_recording(control.focused),
+            Action::PlayMacro => macros.play_selected(ev.count.unwrap_or(1), &control),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!("Cannot apply {action:?}: {e}");
+            macros.error = Some(e.to_string());
+            continue;
+        }
+        match control.input_mode {
+            InputMode::Travel => travel_mode::handle_action(
+                ev,
+                (&control, &register),
+                &mut control_events,
+                &mut bubble_commands,
+            ),
+            InputMode::Edit(_) => edit_mode::handle_action(action, &mut control_events),
+            InputMode::Visual => visual_mode::handle_action(action, &mut control_events),
+            InputMode::Link { from, to } => {
+                link_mode::handle_action(action, (from, to), &mut control_events)
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Selecting all bubbles in a rectangle by dragging with Shift held
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use super::picking::{bubble_at, CursorWorld};
+use crate::{camera::ControlEvent, GraphBubble};
+
+/// A selection rectangle being dragged
+#[derive(Resource, Default)]
+pub struct BoxSelect {
+    /// Corner where the drag started in world coordinates, `None` if there is no drag
+    pub start: Option<Vec2>,
+    /// Current opposite corner in world coordinates
+    pub end: Vec2,
+}
+
+impl BoxSelect {
+    pub fn is_active(&self) -> bool {
+        self.start.is_some()
+    }
+
+    pub fn rect(&self) -> Option<Rect> {
+        Some(Rect::from_corners(self.start?, self.end))
+    }
+}
+
+/// Shift + left click toggles the bubble under the cursor in the selection. On empty canvas, it
+/// starts a rectangle that adds the bubbles inside it to the selection on release.
+pub fn handle_box_select(
+    mut contexts: EguiContexts,
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: CursorWorld,
+    bubbles: Query<(&GraphBubble, &Transform)>,
+    mut select: ResMut<BoxSelect>,
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    let Some(cursor) = cursor.position() else {
+        return;
+    };
+
+    if let Some(rect) = select.rect() {
+        select.end = cursor;
+        if buttons.just_released(MouseButton::Left) {
+            select.start = None;
+            let inside = bubbles
+                .iter()
+                .filter(|(_, tfm)| rect.contains(tfm.translation.truncate()))
+                .map(|(id, _)| id.0)
+                .collect::<Vec<_>>();
+            control_events.send(ControlEvent::ExtendSelection(inside));
+        }
+        return;
+    }
+
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift
+        && buttons.just_pressed(MouseButton::Left)
+        && !contexts.ctx_mut().is_pointer_over_area()
+    {
+        match bubble_at(cursor, bubbles.iter()) {
+            Some(id) => control_events.send(ControlEvent::ToggleSelect(id)),
+            None => {
+                select.start = Some(cursor);
+                select.end = cursor;
+            }
+        }
+    }
+}
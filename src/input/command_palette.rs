@@ -0,0 +1,31 @@
+//! State of the command palette, which lists every [`Action`](super::Action)
+//!
+//! The palette itself is drawn by the UI plugin.
+use bevy::prelude::*;
+
+/// The command palette, opened with [`Action::CommandPalette`](super::Action::CommandPalette)
+#[derive(Resource, Default)]
+pub struct CommandPalette {
+    pub open: bool,
+    /// Text typed to filter the actions
+    pub query: String,
+    /// Index of the highlighted entry among the filtered actions
+    pub selected: usize,
+}
+
+impl CommandPalette {
+    /// Opens the palette with an empty query, or closes it
+    pub fn toggle(&mut self) {
+        if self.open {
+            self.close();
+        } else {
+            self.open = true;
+            self.query.clear();
+            self.selected = 0;
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+}
@@ -0,0 +1,115 @@
+//! Opening a context menu for whatever is under the cursor on right click or a long press
+//!
+//! The menu itself is drawn by the UI plugin.
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use super::picking::{bubble_at, edge_at, CursorWorld};
+use crate::{
+    bubbles::{BubbleId, Bubbles, EdgeId},
+    camera::ControlEvent,
+    BubbleConnection, GraphBubble,
+};
+
+/// What a context menu was opened on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuTarget {
+    Bubble(BubbleId),
+    /// An edge and its endpoints as `(from, to)` when the menu was opened
+    ///
+    /// Edge IDs are reused, so the endpoints tell whether the ID still refers to the same edge.
+    Edge {
+        edge: EdgeId,
+        from: BubbleId,
+        to: BubbleId,
+    },
+    Canvas,
+}
+
+/// The open context menu, if any
+#[derive(Resource, Default)]
+pub struct ContextMenu {
+    /// `None` if the menu is closed
+    pub target: Option<MenuTarget>,
+    /// Where the menu is shown, in logical pixels from the top left of the window
+    pub screen_pos: Vec2,
+    /// The point that was clicked in world coordinates, e.g., for spawning a bubble there
+    pub world_pos: Vec2,
+}
+
+impl ContextMenu {
+    pub fn is_open(&self) -> bool {
+        self.target.is_some()
+    }
+
+    pub fn close(&mut self) {
+        self.target = None;
+    }
+}
+
+/// Opens the context menu on right click or [`ControlEvent::OpenContextMenu`], targeting the
+/// bubble or edge under the point, or the canvas if there is neither. Left click or a touch
+/// outside the menu and Esc close it.
+pub fn handle_context_menu(
+    mut contexts: EguiContexts,
+    (buttons, keys, touches): (Res<Input<MouseButton>>, Res<Input<KeyCode>>, Res<Touches>),
+    mut control_events: EventReader<ControlEvent>,
+    cursor: CursorWorld,
+    (bubbles, transforms): (Res<Bubbles>, Query<(&GraphBubble, &Transform)>),
+    connections: Query<&BubbleConnection>,
+    mut menu: ResMut<ContextMenu>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        menu.close();
+    }
+    let requested = control_events
+        .iter()
+        .filter_map(|ev| match ev {
+            ControlEvent::OpenContextMenu(screen_pos) => Some(*screen_pos),
+            _ => None,
+        })
+        .last();
+    if contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+    if buttons.just_pressed(MouseButton::Left) || touches.any_just_pressed() {
+        menu.close();
+    }
+    let screen_pos = if buttons.just_pressed(MouseButton::Right) {
+        cursor.screen_position()
+    } else {
+        requested
+    };
+    let Some(screen_pos) = screen_pos else {
+        return;
+    };
+    let Some(world_pos) = cursor.to_world(screen_pos) else {
+        return;
+    };
+
+    let target = bubble_at(world_pos, transforms.iter())
+        .map(MenuTarget::Bubble)
+        .or_else(|| {
+            let position = |id| {
+                let (_, tfm) = transforms.get(bubbles.entity(id)?).ok()?;
+                Some(tfm.translation.truncate())
+            };
+            let edges = connections.iter().filter_map(|conn| {
+                let edge = bubbles.find_edge(conn.0, conn.1).ok()?;
+                let target = MenuTarget::Edge {
+                    edge,
+                    from: conn.0,
+                    to: conn.1,
+                };
+                Some((target, position(conn.0)?, position(conn.1)?))
+            });
+            edge_at(world_pos, edges)
+        })
+        .unwrap_or(MenuTarget::Canvas);
+
+    *menu = ContextMenu {
+        target: Some(target),
+        screen_pos,
+        world_pos,
+    };
+}
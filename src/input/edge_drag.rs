@@ -0,0 +1,63 @@
+//! Connecting bubbles by dragging from one to another with Ctrl held
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use super::picking::{bubble_at, CursorWorld};
+use crate::{
+    bubbles::{BubbleCommand, BubbleId},
+    camera::ControlEvent,
+    GraphBubble,
+};
+
+/// An edge being dragged out of a bubble
+#[derive(Resource, Default)]
+pub struct EdgeDrag {
+    /// The bubble the drag started from, `None` if there is no drag
+    pub from: Option<BubbleId>,
+    /// Current end of the drag in world coordinates
+    pub to: Vec2,
+}
+
+impl EdgeDrag {
+    pub fn is_active(&self) -> bool {
+        self.from.is_some()
+    }
+}
+
+/// Starts a drag on Ctrl + left click on a bubble and finishes it on release
+///
+/// Releasing over another bubble makes it a child of the bubble the drag started from. Releasing
+/// on empty canvas spawns a new child there.
+pub fn handle_edge_drag(
+    mut contexts: EguiContexts,
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: CursorWorld,
+    bubbles: Query<(&GraphBubble, &Transform)>,
+    mut drag: ResMut<EdgeDrag>,
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    let Some(cursor) = cursor.position() else {
+        return;
+    };
+
+    if let Some(from) = drag.from {
+        drag.to = cursor;
+        if buttons.just_released(MouseButton::Left) {
+            drag.from = None;
+            let command = match bubble_at(cursor, bubbles.iter()) {
+                Some(to) => BubbleCommand::Connect(to, from),
+                None => BubbleCommand::SpawnChildAt(from, cursor),
+            };
+            control_events.send(ControlEvent::Edit(command));
+        }
+        return;
+    }
+
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && buttons.just_pressed(MouseButton::Left) && !contexts.ctx_mut().is_pointer_over_area()
+    {
+        drag.from = bubble_at(cursor, bubbles.iter());
+        drag.to = cursor;
+    }
+}
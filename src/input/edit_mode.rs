@@ -0,0 +1,13 @@
+use bevy::prelude::*;
+
+use super::Action;
+use crate::{camera::ControlEvent, cursor_control::InputMode};
+
+/// Returns to travel mode on [`Action::Travel`]
+///
+/// Text editing itself is not implemented yet.
+pub fn handle_action(action: Action, control_events: &mut EventWriter<ControlEvent>) {
+    if action == Action::Travel {
+        control_events.send(ControlEvent::ChangeMode(InputMode::Travel));
+    }
+}
@@ -0,0 +1,316 @@
+//! Gamepad navigation and editing
+//!
+//! The left stick pans the view and the triggers zoom, with the dead zone and sensitivities of
+//! [`GamepadConfig`]. Buttons are turned into [`ActionEvent`]s, so they behave like the keys bound
+//! to the same actions. The plugin only reads bevy's gamepad resources, so it runs without a
+//! window and can be driven by sending [`GamepadEvent`]s, as the tests below do.
+use bevy::{
+    input::gamepad::{
+        GamepadAxisType, GamepadButtonType, GamepadConnection, GamepadConnectionEvent,
+        GamepadEvent, GamepadInfo,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{Action, ActionEvent, Direction, Keymap};
+use crate::{camera::ControlEvent, cursor_control::CursorControl, session::LiveInput};
+
+/// Reads connected gamepads, see the [module docs](self)
+///
+/// Needs bevy's [`InputPlugin`](bevy::input::InputPlugin) for the gamepad resources.
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ControlEvent>()
+            .add_event::<ActionEvent>()
+            .init_resource::<CursorControl>()
+            .init_resource::<Keymap>()
+            .add_systems(
+                Update,
+                (handle_gamepad_sticks, handle_gamepad_buttons).in_set(LiveInput),
+            );
+    }
+}
+
+/// Gamepad settings, stored in the keymap file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    /// Stick and trigger deflections below this fraction are ignored
+    pub dead_zone: f32,
+    /// Speed of panning with the stick fully deflected, in pixels per second
+    pub pan_sensitivity: f32,
+    /// Change of the camera scale per second with a trigger fully pressed
+    pub zoom_sensitivity: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.2,
+            pan_sensitivity: 800.,
+            zoom_sensitivity: 2.,
+        }
+    }
+}
+
+impl GamepadConfig {
+    /// Scales `value` so that the dead zone maps to 0 and full deflection stays 1
+    pub fn apply_dead_zone(&self, value: f32) -> f32 {
+        let dead_zone = self.dead_zone.clamp(0., 0.99);
+        let magnitude = (value.abs() - dead_zone).max(0.) / (1. - dead_zone);
+        magnitude.min(1.).copysign(value)
+    }
+
+    /// Applies the dead zone to the length of a stick vector, keeping its direction
+    pub fn apply_radial_dead_zone(&self, stick: Vec2) -> Vec2 {
+        let length = stick.length();
+        if length == 0. {
+            return Vec2::ZERO;
+        }
+        stick / length * self.apply_dead_zone(length)
+    }
+}
+
+/// Buttons and the actions they trigger
+///
+/// A button triggers the first of its actions that does something in the current mode, e.g.,
+/// south adds a child in travel mode and confirms the link in link mode.
+pub const BUTTONS: [(GamepadButtonType, &[Action]); 8] = [
+    (
+        GamepadButtonType::DPadLeft,
+        &[Action::Navigate(Direction::Left)],
+    ),
+    (
+        GamepadButtonType::DPadRight,
+        &[Action::Navigate(Direction::Right)],
+    ),
+    (
+        GamepadButtonType::DPadUp,
+        &[Action::Navigate(Direction::Up)],
+    ),
+    (
+        GamepadButtonType::DPadDown,
+        &[Action::Navigate(Direction::Down)],
+    ),
+    (
+        GamepadButtonType::South,
+        &[Action::AddChild, Action::ConfirmLink],
+    ),
+    (
+        GamepadButtonType::West,
+        &[Action::DeleteFocused, Action::Delete],
+    ),
+    (GamepadButtonType::North, &[Action::Visual, Action::Travel]),
+    (
+        GamepadButtonType::East,
+        &[Action::Travel, Action::ClearSelection],
+    ),
+];
+
+/// The action `button` triggers in `mode`, see [`BUTTONS`]
+pub fn button_action(button: GamepadButtonType, mode: &str) -> Option<Action> {
+    let (_, actions) = BUTTONS.iter().find(|(bound, _)| *bound == button)?;
+    actions
+        .iter()
+        .find(|action| action.modes().contains(&mode))
+        .copied()
+}
+
+/// Pans with the left stick and zooms with the triggers, in every mode
+pub fn handle_gamepad_sticks(
+    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    (axes, button_axes): (Res<Axis<GamepadAxis>>, Res<Axis<GamepadButton>>),
+    keymap: Res<Keymap>,
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    let config = keymap.gamepad();
+    let dt = time.delta_seconds();
+    for gamepad in gamepads.iter() {
+        let axis = |axis_type| axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.);
+        let stick = config.apply_radial_dead_zone(Vec2::new(
+            axis(GamepadAxisType::LeftStickX),
+            axis(GamepadAxisType::LeftStickY),
+        ));
+        if stick != Vec2::ZERO {
+            // Pans are in screen coordinates, where y points down
+            let v = Vec2::new(stick.x, -stick.y);
+            control_events.send(ControlEvent::Pan(v * config.pan_sensitivity * dt));
+        }
+
+        let trigger = |button_type| {
+            let value = button_axes
+                .get(GamepadButton::new(gamepad, button_type))
+                .unwrap_or(0.);
+            config.apply_dead_zone(value)
+        };
+        let zoom =
+            trigger(GamepadButtonType::RightTrigger2) - trigger(GamepadButtonType::LeftTrigger2);
+        if zoom != 0. {
+            control_events.send(ControlEvent::ZoomIn(zoom * config.zoom_sensitivity * dt));
+        }
+    }
+}
+
+/// Sends the actions of the buttons pressed this frame, see [`BUTTONS`]
+pub fn handle_gamepad_buttons(
+    buttons: Res<Input<GamepadButton>>,
+    control: Res<CursorControl>,
+    mut actions: EventWriter<ActionEvent>,
+) {
+    let mode = control.input_mode.as_str();
+    for button in buttons.get_just_pressed() {
+        if let Some(action) = button_action(button.button_type, mode) {
+            actions.send(action.into());
+        }
+    }
+}
+
+/// An event that connects `gamepad`, for driving the plugin without a real gamepad
+pub fn connect_event(gamepad: Gamepad, name: &str) -> GamepadEvent {
+    GamepadEvent::Connection(GamepadConnectionEvent::new(
+        gamepad,
+        GamepadConnection::Connected(GamepadInfo {
+            name: name.to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        ecs::event::ManualEventReader,
+        input::{
+            gamepad::{GamepadAxisChangedEvent, GamepadButtonChangedEvent},
+            InputPlugin,
+        },
+        time::TimeUpdateStrategy,
+    };
+
+    use super::*;
+    use crate::{cursor_control::InputMode, headless_app};
+
+    const FRAME: Duration = Duration::from_millis(20);
+
+    /// Sends synthetic gamepad events and collects what the plugin turns them into
+    struct Harness {
+        app: App,
+        gamepad: Gamepad,
+        control_reader: ManualEventReader<ControlEvent>,
+        action_reader: ManualEventReader<ActionEvent>,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let mut app = headless_app();
+            app.add_plugins((InputPlugin, GamepadPlugin))
+                .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+            let gamepad = Gamepad::new(0);
+            let mut harness = Self {
+                app,
+                gamepad,
+                control_reader: default(),
+                action_reader: default(),
+            };
+            harness.frame(vec![connect_event(gamepad, "synthetic")]);
+            harness
+        }
+
+        /// Runs a frame with `events` and returns the control events and actions it sent
+        fn frame(&mut self, events: Vec<GamepadEvent>) -> (Vec<ControlEvent>, Vec<Action>) {
+            self.app.world.send_event_batch(events);
+            self.app.update();
+            let world = &self.app.world;
+            let controls = self
+                .control_reader
+                .iter(world.resource::<Events<ControlEvent>>())
+                .cloned()
+                .collect();
+            let actions = self
+                .action_reader
+                .iter(world.resource::<Events<ActionEvent>>())
+                .map(|ev| ev.action)
+                .collect();
+            (controls, actions)
+        }
+
+        fn axis(&self, axis_type: GamepadAxisType, value: f32) -> GamepadEvent {
+            GamepadEvent::Axis(GamepadAxisChangedEvent::new(self.gamepad, axis_type, value))
+        }
+
+        fn button(&self, button_type: GamepadButtonType, value: f32) -> GamepadEvent {
+            GamepadEvent::Button(GamepadButtonChangedEvent::new(
+                self.gamepad,
+                button_type,
+                value,
+            ))
+        }
+    }
+
+    #[test]
+    fn sticks_pan_and_triggers_zoom_outside_the_dead_zone() {
+        let mut harness = Harness::new();
+        let config = GamepadConfig::default();
+        let dt = FRAME.as_secs_f32();
+
+        let within_dead_zone = vec![
+            harness.axis(GamepadAxisType::LeftStickX, 0.1),
+            harness.button(GamepadButtonType::RightTrigger2, 0.15),
+        ];
+        let (controls, actions) = harness.frame(within_dead_zone);
+        assert!(controls.is_empty(), "{controls:?}");
+        assert!(actions.is_empty(), "{actions:?}");
+
+        let (controls, _) = harness.frame(vec![
+            harness.axis(GamepadAxisType::LeftStickX, 1.),
+            harness.axis(GamepadAxisType::LeftStickY, 0.5),
+            harness.button(GamepadButtonType::RightTrigger2, 0.),
+        ]);
+        // Beyond full deflection, so the stick pans at full speed, with y pointing down
+        let expected = Vec2::new(1., -0.5).normalize() * config.pan_sensitivity * dt;
+        match controls.as_slice() {
+            [ControlEvent::Pan(pan)] => assert!(pan.abs_diff_eq(expected, 1e-4), "{pan}"),
+            other => panic!("expected a pan, got {other:?}"),
+        }
+
+        let (controls, _) = harness.frame(vec![
+            harness.axis(GamepadAxisType::LeftStickX, 0.),
+            harness.axis(GamepadAxisType::LeftStickY, 0.),
+            harness.button(GamepadButtonType::RightTrigger2, 1.),
+        ]);
+        let expected = config.zoom_sensitivity * dt;
+        match controls.as_slice() {
+            [ControlEvent::ZoomIn(zoom)] => assert!((zoom - expected).abs() < 1e-5, "{zoom}"),
+            other => panic!("expected a zoom, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn buttons_trigger_actions_of_the_current_mode() {
+        let mut harness = Harness::new();
+
+        let (_, actions) = harness.frame(vec![
+            harness.button(GamepadButtonType::South, 1.),
+            harness.button(GamepadButtonType::DPadLeft, 1.),
+        ]);
+        assert_eq!(actions.len(), 2, "{actions:?}");
+        assert!(actions.contains(&Action::AddChild));
+        assert!(actions.contains(&Action::Navigate(Direction::Left)));
+
+        // Held buttons do not repeat
+        let (_, actions) = harness.frame(vec![]);
+        assert!(actions.is_empty(), "{actions:?}");
+
+        harness.app.world.resource_mut::<CursorControl>().input_mode = InputMode::Visual;
+        let (_, actions) = harness.frame(vec![
+            harness.button(GamepadButtonType::South, 0.),
+            harness.button(GamepadButtonType::West, 1.),
+        ]);
+        assert_eq!(actions, [Action::Delete]);
+    }
+}
@@ -0,0 +1,701 @@
+//! Key bindings for each input mode, stored in a RON config file
+//!
+//! A binding maps a key sequence to an [`Action`]. A sequence is one or more chords separated by
+//! spaces, and a chord is a key with optional modifiers, e.g., `"ctrl+shift+p"` or `"g g"`. The
+//! file is reloaded whenever it changes on disk, and problems with it are listed in the UI.
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_egui::EguiContexts;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{Action, ActionEvent, Direction, GamepadConfig};
+use crate::{cursor_control::CursorControl, presets::modified_time};
+
+pub const DEFAULT_KEYMAP_PATH: &str = "config/keymap.ron";
+/// How often the keymap file is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+/// Modes that bindings can be given for, see [`InputMode::as_str`](crate::cursor_control::InputMode::as_str)
+pub const MODES: [&str; 4] = ["travel", "edit", "link", "visual"];
+
+#[derive(Error, Debug)]
+pub enum KeymapError {
+    #[error("could not access {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("could not parse {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: ron::error::SpannedError,
+    },
+    #[error("could not serialize keymap: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+/// Contents of the keymap file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeymapFile {
+    /// Speed of panning with the keyboard, in pixels per second
+    pub pan_speed: f32,
+    /// Seconds to wait for the next key of a sequence before dropping it
+    pub sequence_timeout: f32,
+    /// Bindings from key sequence to action, by mode name
+    ///
+    /// Modes missing from the file keep their default bindings.
+    pub modes: BTreeMap<String, BTreeMap<String, Action>>,
+    /// What scrolling by pixels does
+    pub pixel_scroll: PixelScroll,
+    pub gamepad: GamepadConfig,
+}
+
+/// What scrolling by pixels rather than lines does
+///
+/// Trackpads scroll by pixels and zoom by pinching, but so do some mice with smooth wheels, which
+/// have no other way to zoom.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PixelScroll {
+    /// Move the view, like dragging
+    #[default]
+    Pan,
+    /// Zoom, like a mouse wheel scrolling by lines
+    Zoom,
+}
+
+impl Default for KeymapFile {
+    /// The built-in bindings, used when there is no keymap file yet
+    fn default() -> Self {
+        let arrows = |action: fn(Direction) -> Action| {
+            [
+                ("left", Direction::Left),
+                ("right", Direction::Right),
+                ("up", Direction::Up),
+                ("down", Direction::Down),
+            ]
+            .map(|(key, dir)| (key.to_string(), action(dir)))
+        };
+        let bindings = |pairs: &[(&str, Action)]| {
+            pairs
+                .iter()
+                .map(|(key, action)| (key.to_string(), *action))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let mut travel = bindings(&[
+            ("i", Action::Edit),
+            ("shift+l", Action::Link),
+            ("v", Action::Visual),
+            ("=", Action::ZoomIn),
+            ("-", Action::ZoomOut),
+            ("a", Action::AddChild),
+            ("x", Action::DeleteFocused),
+            ("d d", Action::DeleteSubtree),
+            ("y y", Action::YankSubtree),
+            ("p", Action::Paste),
+            ("o", Action::NewSibling),
+            ("shift+o", Action::AddChild),
+            (".", Action::RepeatChange),
+            ("h", Action::Navigate(Direction::Left)),
+            ("j", Action::Navigate(Direction::Down)),
+            ("k", Action::Navigate(Direction::Up)),
+            ("l", Action::Navigate(Direction::Right)),
+            ("ctrl+l", Action::SettleLayout),
+            ("q", Action::RecordMacro),
+            ("shift+q", Action::PlayMacro),
+        ]);
+        travel.extend(arrows(Action::Pan));
+        let mut edit = bindings(&[("esc", Action::Travel)]);
+        let mut link = bindings(&[("enter", Action::ConfirmLink), ("esc", Action::Travel)]);
+        link.extend(arrows(Action::Navigate));
+        let mut visual = bindings(&[
+            ("d", Action::Delete),
+            ("delete", Action::Delete),
+            ("c", Action::ConnectToFocused),
+            ("g", Action::Group),
+            ("esc", Action::Travel),
+        ]);
+        visual.extend(arrows(Action::Navigate));
+        visual.extend((0..6).map(|idx| ((idx + 1).to_string(), Action::Recolor(idx))));
+
+        for mode in [&mut travel, &mut edit, &mut link, &mut visual] {
+            mode.insert("ctrl+p".to_string(), Action::CommandPalette);
+        }
+
+        Self {
+            pan_speed: 500.,
+            sequence_timeout: 1.,
+            modes: [
+                ("travel", travel),
+                ("edit", edit),
+                ("link", link),
+                ("visual", visual),
+            ]
+            .into_iter()
+            .map(|(mode, bindings)| (mode.to_string(), bindings))
+            .collect(),
+            pixel_scroll: PixelScroll::default(),
+            gamepad: GamepadConfig::default(),
+        }
+    }
+}
+
+/// A key together with the modifiers held with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl Chord {
+    /// The chord of `key` with the modifiers currently held
+    pub fn with_held_modifiers(key: KeyCode, keys: &Input<KeyCode>) -> Self {
+        Self {
+            key,
+            ctrl: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+            shift: keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+            alt: keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]),
+            meta: keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight]),
+        }
+    }
+
+    /// Parses a chord such as `"ctrl+p"`, ignoring case
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.to_lowercase();
+        let mut parts = text.split('+').collect::<Vec<_>>();
+        let key_name = parts.pop().unwrap_or_default();
+        let key = key_from_name(key_name).ok_or_else(|| format!("unknown key '{key_name}'"))?;
+        let mut chord = Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            meta: false,
+        };
+        for modifier in parts {
+            match modifier {
+                "ctrl" | "control" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" => chord.alt = true,
+                "meta" | "super" | "cmd" => chord.meta = true,
+                _ => return Err(format!("unknown modifier '{modifier}'")),
+            }
+        }
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "ctrl"),
+            (self.shift, "shift"),
+            (self.alt, "alt"),
+            (self.meta, "meta"),
+        ] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        match key_name(self.key) {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{:?}", self.key),
+        }
+    }
+}
+
+/// Parses a sequence of chords separated by whitespace, such as `"g g"`
+pub fn parse_sequence(text: &str) -> Result<Vec<Chord>, String> {
+    let chords = text
+        .split_whitespace()
+        .map(Chord::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if chords.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(chords)
+}
+
+/// Formats a sequence the way [`parse_sequence`] reads it
+pub fn format_sequence(chords: &[Chord]) -> String {
+    chords
+        .iter()
+        .map(Chord::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What a key sequence does in a mode
+#[derive(Debug, PartialEq)]
+pub enum Lookup {
+    Bound(Action),
+    /// The sequence is the start of one or more bindings
+    Prefix,
+    Unbound,
+}
+
+/// Key bindings loaded from [`Keymap::path`]
+#[derive(Resource)]
+pub struct Keymap {
+    pub path: PathBuf,
+    file: KeymapFile,
+    /// Parsed bindings of each mode, in the order of the file
+    bindings: HashMap<String, Vec<(Vec<Chord>, Action)>>,
+    /// Modification time of the file when it was last loaded
+    loaded_at: Option<SystemTime>,
+    reload_timer: Timer,
+    /// Error from the last load or save, shown in the UI
+    pub error: Option<String>,
+    /// Unknown keys, modes and actions, and conflicting bindings, shown in the UI
+    pub problems: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEYMAP_PATH)
+    }
+}
+
+impl Keymap {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut keymap = Self {
+            path: path.into(),
+            file: KeymapFile::default(),
+            bindings: HashMap::new(),
+            loaded_at: None,
+            reload_timer: Timer::new(RELOAD_INTERVAL, TimerMode::Repeating),
+            error: None,
+            problems: Vec::new(),
+        };
+        keymap.compile();
+        keymap
+    }
+
+    pub fn pan_speed(&self) -> f32 {
+        self.file.pan_speed
+    }
+
+    pub fn sequence_timeout(&self) -> f32 {
+        self.file.sequence_timeout
+    }
+
+    pub fn pixel_scroll(&self) -> PixelScroll {
+        self.file.pixel_scroll
+    }
+
+    /// For changing in the UI, written to the file by [`Keymap::write`]
+    pub fn pixel_scroll_mut(&mut self) -> &mut PixelScroll {
+        &mut self.file.pixel_scroll
+    }
+
+    pub fn gamepad(&self) -> &GamepadConfig {
+        &self.file.gamepad
+    }
+
+    /// Gamepad settings for changing in the UI, written to the file by [`Keymap::write`]
+    pub fn gamepad_mut(&mut self) -> &mut GamepadConfig {
+        &mut self.file.gamepad
+    }
+
+    /// The valid bindings of `mode`
+    pub fn bindings(&self, mode: &str) -> &[(Vec<Chord>, Action)] {
+        self.bindings.get(mode).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns what `chords` do in `mode`
+    pub fn lookup(&self, mode: &str, chords: &[Chord]) -> Lookup {
+        let mut lookup = Lookup::Unbound;
+        for (sequence, action) in self.bindings(mode) {
+            if sequence == chords {
+                return Lookup::Bound(*action);
+            }
+            if sequence.starts_with(chords) {
+                lookup = Lookup::Prefix;
+            }
+        }
+        lookup
+    }
+
+    /// The sequences bound to `action` in `mode`
+    pub fn sequences_of(&self, mode: &str, action: Action) -> impl Iterator<Item = &[Chord]> {
+        self.bindings(mode)
+            .iter()
+            .filter(move |(_, bound)| *bound == action)
+            .map(|(sequence, _)| sequence.as_slice())
+    }
+
+    /// True if a single-chord binding of `action` in `mode` is currently held down
+    pub fn is_held(&self, mode: &str, action: Action, keys: &Input<KeyCode>) -> bool {
+        self.bindings(mode).iter().any(|(sequence, bound)| {
+            *bound == action
+                && matches!(sequence.as_slice(), [chord]
+                    if keys.pressed(chord.key)
+                        && Chord::with_held_modifiers(chord.key, keys) == *chord)
+        })
+    }
+
+    /// Writes the current keymap to the file, creating its directory if necessary
+    pub fn write(&mut self) -> Result<(), KeymapError> {
+        let io_err = |source| KeymapError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let text = ron::ser::to_string_pretty(&self.file, ron::ser::PrettyConfig::default())?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        fs::write(&self.path, text).map_err(io_err)?;
+        // Do not reload what was just written
+        self.loaded_at = modified_time(&self.path);
+        Ok(())
+    }
+
+    /// Reloads the file if it has changed since it was last loaded
+    ///
+    /// Returns true if the keymap was reloaded. A missing file keeps the current keymap.
+    pub fn reload_if_changed(&mut self) -> Result<bool, KeymapError> {
+        let Some(modified) = modified_time(&self.path) else {
+            return Ok(false);
+        };
+        if self.loaded_at == Some(modified) {
+            return Ok(false);
+        }
+        // Only try each version of the file once, even if it fails to parse
+        self.loaded_at = Some(modified);
+
+        let text = fs::read_to_string(&self.path).map_err(|source| KeymapError::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        self.file = ron::from_str(&text).map_err(|source| KeymapError::Parse {
+            path: self.path.clone(),
+            source,
+        })?;
+        self.compile();
+        Ok(true)
+    }
+
+    /// Parses the bindings of the file, keeping the valid ones and listing problems with the rest
+    fn compile(&mut self) {
+        let defaults = KeymapFile::default();
+        self.bindings.clear();
+        self.problems.clear();
+
+        for mode in self.file.modes.keys() {
+            if !MODES.contains(&mode.as_str()) {
+                self.problems.push(format!(
+                    "unknown mode '{mode}', expected one of {}",
+                    MODES.join(", ")
+                ));
+            }
+        }
+
+        for mode in MODES {
+            let Some(source) = self.file.modes.get(mode).or(defaults.modes.get(mode)) else {
+                continue;
+            };
+            let mut bindings: Vec<(Vec<Chord>, Action)> = Vec::new();
+            for (text, action) in source {
+                let problem = match parse_sequence(text) {
+                    Err(e) => Some(e),
+                    Ok(_) if !action.modes().contains(&mode) => {
+                        Some(format!("{action:?} does nothing in this mode"))
+                    }
+                    Ok(chords) => match action.check() {
+                        Some(e) => Some(e),
+                        None => {
+                            bindings.push((chords, *action));
+                            None
+                        }
+                    },
+                };
+                if let Some(problem) = problem {
+                    self.problems.push(format!("{mode}: '{text}': {problem}"));
+                }
+            }
+            self.problems.extend(conflicts(&bindings).map(|(a, b)| {
+                format!(
+                    "{mode}: '{}' hides '{}'",
+                    format_sequence(a),
+                    format_sequence(b)
+                )
+            }));
+            self.bindings.insert(mode.to_string(), bindings);
+        }
+    }
+}
+
+/// Pairs of bindings where the first prevents the second from ever being reached, because the
+/// sequences are the same or the first is the start of the second
+fn conflicts(
+    bindings: &[(Vec<Chord>, Action)],
+) -> impl Iterator<Item = (&Vec<Chord>, &Vec<Chord>)> {
+    bindings.iter().enumerate().flat_map(move |(idx, (a, _))| {
+        bindings[idx + 1..].iter().filter_map(move |(b, _)| {
+            if b.starts_with(a) {
+                Some((a, b))
+            } else if a.starts_with(b) {
+                Some((b, a))
+            } else {
+                None
+            }
+        })
+    })
+}
+
+/// Hot-reloads the keymap file
+pub fn reload_keymap(time: Res<Time>, mut keymap: ResMut<Keymap>) {
+    if !keymap.reload_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    match keymap.reload_if_changed() {
+        Ok(true) => keymap.error = None,
+        Ok(false) => {}
+        Err(e) => keymap.error = Some(e.to_string()),
+    }
+}
+
+/// Largest count that can be typed before a binding
+const MAX_COUNT: usize = 999;
+
+/// Keys pressed so far of a sequence that is not complete yet
+#[derive(Resource, Default)]
+pub struct PendingKeys {
+    /// Number typed before the sequence, e.g., 3 in `3j`
+    pub count: Option<usize>,
+    pub chords: Vec<Chord>,
+    /// Mode the sequence was started in
+    mode: &'static str,
+    /// Time of the last key press, in seconds since startup
+    last_press: f32,
+}
+
+impl PendingKeys {
+    pub fn is_empty(&self) -> bool {
+        self.count.is_none() && self.chords.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.count = None;
+        self.chords.clear();
+    }
+
+    /// Adds the digit of `chord` to the count, if it is a digit that can be part of a count
+    ///
+    /// Only unbound digits count, so that digits can still be bound, and a count cannot start with
+    /// zero.
+    fn push_count(&mut self, chord: Chord, keymap: &Keymap) -> bool {
+        let Some(digit) = DIGITS.iter().position(|key| *key == chord.key) else {
+            return false;
+        };
+        let plain = !(chord.ctrl || chord.shift || chord.alt || chord.meta);
+        if !plain
+            || !self.chords.is_empty()
+            || (digit == 0 && self.count.is_none())
+            || keymap.lookup(self.mode, &[chord]) != Lookup::Unbound
+        {
+            return false;
+        }
+        self.count = Some((self.count.unwrap_or(0) * 10 + digit).min(MAX_COUNT));
+        true
+    }
+}
+
+/// Turns key presses into [`ActionEvent`]s according to the [`Keymap`] of the current mode
+///
+/// Unbound digits before a sequence are its count. A key that does not continue the pending
+/// sequence starts a new one, dropping the count.
+pub fn dispatch_keys(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    (keymap, control): (Res<Keymap>, Res<CursorControl>),
+    mut pending: ResMut<PendingKeys>,
+    mut actions: EventWriter<ActionEvent>,
+) {
+    // If egui wants keyboard input, do not create actions
+    if contexts.ctx_mut().wants_keyboard_input() {
+        if !pending.is_empty() {
+            pending.clear();
+        }
+        return;
+    }
+
+    let mode = control.input_mode.as_str();
+    let now = time.elapsed_seconds();
+    if !pending.is_empty()
+        && (pending.mode != mode || now - pending.last_press > keymap.sequence_timeout())
+    {
+        pending.clear();
+    }
+
+    for key in keys.get_just_pressed().filter(|key| !is_modifier(**key)) {
+        let chord = Chord::with_held_modifiers(*key, &keys);
+        pending.mode = mode;
+        pending.last_press = now;
+        if pending.push_count(chord, &keymap) {
+            continue;
+        }
+        pending.chords.push(chord);
+
+        let mut lookup = keymap.lookup(mode, &pending.chords);
+        if lookup == Lookup::Unbound && pending.chords.len() > 1 {
+            pending.clear();
+            pending.chords.push(chord);
+            lookup = keymap.lookup(mode, &pending.chords);
+        }
+        match lookup {
+            Lookup::Bound(action) => {
+                actions.send(ActionEvent {
+                    action,
+                    count: pending.count,
+                });
+                pending.clear();
+            }
+            Lookup::Prefix => {}
+            Lookup::Unbound => pending.clear(),
+        }
+    }
+}
+
+fn is_modifier(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::ControlLeft
+            | KeyCode::ControlRight
+            | KeyCode::ShiftLeft
+            | KeyCode::ShiftRight
+            | KeyCode::AltLeft
+            | KeyCode::AltRight
+            | KeyCode::SuperLeft
+            | KeyCode::SuperRight
+    )
+}
+
+const LETTERS: [KeyCode; 26] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+];
+
+const DIGITS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// Names of the keys other than letters and digits. The first name of a key is used for display.
+const KEY_NAMES: [(&str, KeyCode); 45] = [
+    ("esc", KeyCode::Escape),
+    ("escape", KeyCode::Escape),
+    ("enter", KeyCode::Return),
+    ("return", KeyCode::Return),
+    ("space", KeyCode::Space),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Back),
+    ("delete", KeyCode::Delete),
+    ("del", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+    ("f7", KeyCode::F7),
+    ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9),
+    ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11),
+    ("f12", KeyCode::F12),
+    ("comma", KeyCode::Comma),
+    (",", KeyCode::Comma),
+    ("period", KeyCode::Period),
+    (".", KeyCode::Period),
+    ("slash", KeyCode::Slash),
+    ("/", KeyCode::Slash),
+    ("minus", KeyCode::Minus),
+    ("-", KeyCode::Minus),
+    ("equals", KeyCode::Equals),
+    ("=", KeyCode::Equals),
+    ("semicolon", KeyCode::Semicolon),
+    (";", KeyCode::Semicolon),
+    ("backslash", KeyCode::Backslash),
+    ("lbracket", KeyCode::BracketLeft),
+    ("rbracket", KeyCode::BracketRight),
+];
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.clone().next()) {
+        if c.is_ascii_lowercase() {
+            return Some(LETTERS[(c as u8 - b'a') as usize]);
+        }
+        if c.is_ascii_digit() {
+            return Some(DIGITS[(c as u8 - b'0') as usize]);
+        }
+    }
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == name)
+        .map(|(_, key)| *key)
+}
+
+fn key_name(key: KeyCode) -> Option<String> {
+    if let Some(idx) = LETTERS.iter().position(|letter| *letter == key) {
+        return Some(char::from(b'a' + idx as u8).to_string());
+    }
+    if let Some(idx) = DIGITS.iter().position(|digit| *digit == key) {
+        return Some(idx.to_string());
+    }
+    KEY_NAMES
+        .iter()
+        .find(|(_, named)| *named == key)
+        .map(|(name, _)| name.to_string())
+}
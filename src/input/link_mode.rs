@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+use super::Action;
+use crate::{
+    bubbles::{BubbleCommand, BubbleId},
+    camera::ControlEvent,
+    cursor_control::InputMode,
+};
+
+/// Moves the link target, connects it or cancels the link
+///
+/// # Arguments
+///
+/// * `from` - Bubble that gets the target as its child
+/// * `to` - Current target
+pub fn handle_action(
+    action: Action,
+    (from, to): (BubbleId, BubbleId),
+    control_events: &mut EventWriter<ControlEvent>,
+) {
+    match action {
+        Action::Travel => control_events.send(ControlEvent::ChangeMode(InputMode::Travel)),
+        Action::ConfirmLink => {
+            control_events.send(ControlEvent::Edit(BubbleCommand::Connect(to, from)));
+            control_events.send(ControlEvent::ChangeMode(InputMode::Travel));
+        }
+        Action::Navigate(dir) => control_events.send(ControlEvent::Navigate { dir, times: 1 }),
+        _ => {}
+    }
+}
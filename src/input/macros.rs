@@ -0,0 +1,856 @@
+//! Recording control events as named macros and playing them back on the selection
+//!
+//! A recording keeps the [`ControlEvent`]s that change the selection, the mode or the graph, and
+//! replaces the bubbles they name with [`BubbleRef`]s: the bubble focused when recording started
+//! or a bubble spawned during the recording. Recordings naming any other bubble are not saved.
+//! Moving the focus with the arrow keys is kept as a direction, which is followed from wherever the
+//! focus is when playing. Edits made with the mouse are sent as [`ControlEvent::Edit`], so they
+//! are recorded too. Playback runs the macro once per selected bubble, starting from it, so the
+//! same edit can be applied to many bubbles. The macros are stored in a RON file, which is
+//! reloaded whenever it changes on disk.
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::Direction;
+use crate::{
+    bubbles::{BubbleCommand, BubbleEvent, BubbleId, Bubbles, EdgeId, EdgeKind, SubtreeCopy},
+    camera::ControlEvent,
+    cursor_control::{BulkOp, Change, CursorControl, InputMode},
+    presets::modified_time,
+};
+
+pub const DEFAULT_MACROS_PATH: &str = "config/macros.ron";
+/// How often the macros file is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+/// Seconds playback waits for the bubbles a macro spawns before giving up
+const MAX_WAIT: f32 = 2.;
+
+#[derive(Error, Debug)]
+pub enum MacroError {
+    #[error("could not access {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("could not parse {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: ron::error::SpannedError,
+    },
+    #[error("could not serialize macros: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("no macro named '{0}'")]
+    NotFound(String),
+    #[error("no macro is selected")]
+    NoneSelected,
+    #[error("a macro is being recorded")]
+    Recording,
+    #[error("a macro is being played")]
+    Playing,
+    #[error("no macro is being recorded")]
+    NotRecording,
+    #[error("macro '{0}' starts from a bubble, but none is selected")]
+    NoTarget(String),
+    #[error("macro '{0}' stopped waiting for the bubbles it spawns")]
+    Stalled(String),
+    #[error(
+        "macro '{name}' was not saved: it names bubble {id}, which it neither started from nor \
+         spawned"
+    )]
+    FixedBubble { name: String, id: BubbleId },
+}
+
+/// A bubble named by a macro step
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BubbleRef {
+    /// The bubble the macro is played on, i.e., the one focused when recording started
+    Start,
+    /// The n-th bubble spawned by the macro
+    Spawned(usize),
+    /// A bubble that was already there
+    ///
+    /// Only used while recording: bubble ids are reused, so saved macros cannot name them.
+    Fixed(BubbleId),
+}
+
+/// Input mode with [`BubbleRef`]s, see [`InputMode`]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ModeStep {
+    Travel,
+    Edit(BubbleRef),
+    Link { from: BubbleRef, to: BubbleRef },
+    Visual,
+}
+
+/// A [`BubbleCommand`] with [`BubbleRef`]s, naming connections by their endpoints as `(from, to)`
+/// since edge ids are reused
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum EditStep {
+    SpawnOrphan(Vec2),
+    SpawnChild(BubbleRef),
+    SpawnChildAt(BubbleRef, Vec2),
+    Connect(BubbleRef, BubbleRef),
+    Disconnect(BubbleRef, BubbleRef),
+    RemoveEdge(BubbleRef, BubbleRef),
+    ReverseEdge(BubbleRef, BubbleRef),
+    Remove(BubbleRef),
+    SetContent(BubbleRef, String),
+    Move(BubbleRef, Vec2),
+    Group(Vec<BubbleRef>),
+    SetColor(BubbleRef, Color),
+    SetPinned(BubbleRef, bool),
+    SetEdgeKind(BubbleRef, BubbleRef, EdgeKind),
+    Paste(BubbleRef, SubtreeCopy),
+    Settle,
+}
+
+impl EditStep {
+    /// The step recording `command`, or `None` if it names a connection missing from `edges`
+    fn record(
+        command: &BubbleCommand,
+        to_ref: impl Fn(BubbleId) -> BubbleRef,
+        edges: &HashMap<EdgeId, (BubbleId, BubbleId)>,
+    ) -> Option<Self> {
+        let edge_refs = |edge| {
+            let (from, to) = edges.get(&edge)?;
+            Some((to_ref(*from), to_ref(*to)))
+        };
+        Some(match command {
+            BubbleCommand::SpawnOrphan(pos) => EditStep::SpawnOrphan(*pos),
+            BubbleCommand::SpawnChild(id) => EditStep::SpawnChild(to_ref(*id)),
+            BubbleCommand::SpawnChildAt(id, pos) => EditStep::SpawnChildAt(to_ref(*id), *pos),
+            BubbleCommand::Connect(from, to) => EditStep::Connect(to_ref(*from), to_ref(*to)),
+            BubbleCommand::Disconnect(from, to) => EditStep::Disconnect(to_ref(*from), to_ref(*to)),
+            BubbleCommand::RemoveEdge(edge) => {
+                let (from, to) = edge_refs(*edge)?;
+                EditStep::RemoveEdge(from, to)
+            }
+            BubbleCommand::ReverseEdge(edge) => {
+                let (from, to) = edge_refs(*edge)?;
+                EditStep::ReverseEdge(from, to)
+            }
+            BubbleCommand::Remove(id) => EditStep::Remove(to_ref(*id)),
+            BubbleCommand::SetContent(id, text) => EditStep::SetContent(to_ref(*id), text.clone()),
+            BubbleCommand::Move(id, pos) => EditStep::Move(to_ref(*id), *pos),
+            BubbleCommand::Group(ids) => {
+                EditStep::Group(ids.iter().map(|id| to_ref(*id)).collect())
+            }
+            BubbleCommand::SetColor(id, color) => EditStep::SetColor(to_ref(*id), *color),
+            BubbleCommand::SetPinned(id, pinned) => EditStep::SetPinned(to_ref(*id), *pinned),
+            BubbleCommand::SetEdgeKind(edge, kind) => {
+                let (from, to) = edge_refs(*edge)?;
+                EditStep::SetEdgeKind(from, to, *kind)
+            }
+            BubbleCommand::Paste(id, copy) => EditStep::Paste(to_ref(*id), copy.clone()),
+            BubbleCommand::Settle => EditStep::Settle,
+        })
+    }
+
+    /// The command to send for the step, or `None` if one of its bubbles or connections does not
+    /// exist yet
+    fn command(
+        &self,
+        resolve: impl Fn(BubbleRef) -> Option<BubbleId>,
+        bubbles: &Bubbles,
+    ) -> Option<BubbleCommand> {
+        let edge = |from, to| bubbles.find_edge(resolve(from)?, resolve(to)?).ok();
+        Some(match self {
+            EditStep::SpawnOrphan(pos) => BubbleCommand::SpawnOrphan(*pos),
+            EditStep::SpawnChild(r) => BubbleCommand::SpawnChild(resolve(*r)?),
+            EditStep::SpawnChildAt(r, pos) => BubbleCommand::SpawnChildAt(resolve(*r)?, *pos),
+            EditStep::Connect(from, to) => BubbleCommand::Connect(resolve(*from)?, resolve(*to)?),
+            EditStep::Disconnect(from, to) => {
+                BubbleCommand::Disconnect(resolve(*from)?, resolve(*to)?)
+            }
+            EditStep::RemoveEdge(from, to) => BubbleCommand::RemoveEdge(edge(*from, *to)?),
+            EditStep::ReverseEdge(from, to) => BubbleCommand::ReverseEdge(edge(*from, *to)?),
+            EditStep::Remove(r) => BubbleCommand::Remove(resolve(*r)?),
+            EditStep::SetContent(r, text) => BubbleCommand::SetContent(resolve(*r)?, text.clone()),
+            EditStep::Move(r, pos) => BubbleCommand::Move(resolve(*r)?, *pos),
+            EditStep::Group(refs) => {
+                BubbleCommand::Group(refs.iter().map(|r| resolve(*r)).collect::<Option<_>>()?)
+            }
+            EditStep::SetColor(r, color) => BubbleCommand::SetColor(resolve(*r)?, *color),
+            EditStep::SetPinned(r, pinned) => BubbleCommand::SetPinned(resolve(*r)?, *pinned),
+            EditStep::SetEdgeKind(from, to, kind) => {
+                BubbleCommand::SetEdgeKind(edge(*from, *to)?, *kind)
+            }
+            EditStep::Paste(r, copy) => BubbleCommand::Paste(resolve(*r)?, copy.clone()),
+            EditStep::Settle => BubbleCommand::Settle,
+        })
+    }
+
+    fn refs(&self) -> Vec<BubbleRef> {
+        match self {
+            EditStep::SpawnOrphan(_) | EditStep::Settle => vec![],
+            EditStep::SpawnChild(r)
+            | EditStep::SpawnChildAt(r, _)
+            | EditStep::Remove(r)
+            | EditStep::SetContent(r, _)
+            | EditStep::Move(r, _)
+            | EditStep::SetColor(r, _)
+            | EditStep::SetPinned(r, _)
+            | EditStep::Paste(r, _) => vec![*r],
+            EditStep::Connect(from, to)
+            | EditStep::Disconnect(from, to)
+            | EditStep::RemoveEdge(from, to)
+            | EditStep::ReverseEdge(from, to)
+            | EditStep::SetEdgeKind(from, to, _) => vec![*from, *to],
+            EditStep::Group(refs) => refs.clone(),
+        }
+    }
+}
+
+/// A recorded [`ControlEvent`] with [`BubbleRef`]s instead of bubble ids
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MacroStep {
+    Select(BubbleRef),
+    ToggleSelect(BubbleRef),
+    ExtendSelection(Vec<BubbleRef>),
+    ClearSelection,
+    /// Moves from the focus at the time of playing, see [`ControlEvent::Navigate`]
+    Navigate {
+        dir: Direction,
+        times: usize,
+    },
+    ChangeMode(ModeStep),
+    Bulk(BulkOp),
+    Change(Change),
+    Yank {
+        depth: Option<usize>,
+    },
+    Edit(EditStep),
+}
+
+impl MacroStep {
+    /// The step recording `ev`, or `None` if macros leave it out
+    fn record(
+        ev: &ControlEvent,
+        to_ref: impl Fn(BubbleId) -> BubbleRef,
+        edges: &HashMap<EdgeId, (BubbleId, BubbleId)>,
+    ) -> Option<Self> {
+        Some(match ev {
+            ControlEvent::Select(id) => MacroStep::Select(to_ref(*id)),
+            ControlEvent::ToggleSelect(id) => MacroStep::ToggleSelect(to_ref(*id)),
+            ControlEvent::ExtendSelection(ids) => {
+                MacroStep::ExtendSelection(ids.iter().map(|id| to_ref(*id)).collect())
+            }
+            ControlEvent::ClearSelection => MacroStep::ClearSelection,
+            ControlEvent::Navigate { dir, times } => MacroStep::Navigate {
+                dir: *dir,
+                times: *times,
+            },
+            ControlEvent::ChangeMode(mode) => MacroStep::ChangeMode(match mode {
+                InputMode::Travel => ModeStep::Travel,
+                InputMode::Edit(id) => ModeStep::Edit(to_ref(*id)),
+                InputMode::Link { from, to } => ModeStep::Link {
+                    from: to_ref(*from),
+                    to: to_ref(*to),
+                },
+                InputMode::Visual => ModeStep::Visual,
+            }),
+            ControlEvent::Bulk(op) => MacroStep::Bulk(op.clone()),
+            ControlEvent::Change(change) => MacroStep::Change(*change),
+            ControlEvent::Yank { depth } => MacroStep::Yank { depth: *depth },
+            ControlEvent::Edit(command) => {
+                MacroStep::Edit(EditStep::record(command, to_ref, edges)?)
+            }
+            // Moving the view and opening menus do not change anything
+            ControlEvent::Pan(_)
+            | ControlEvent::ZoomIn(_)
+            | ControlEvent::ZoomAt { .. }
+            | ControlEvent::OpenContextMenu(_) => return None,
+        })
+    }
+
+    /// The event to send for the step, or `None` if one of its bubbles cannot be resolved yet
+    fn event(
+        &self,
+        resolve: impl Fn(BubbleRef) -> Option<BubbleId>,
+        bubbles: &Bubbles,
+    ) -> Option<ControlEvent> {
+        Some(match self {
+            MacroStep::Select(r) => ControlEvent::Select(resolve(*r)?),
+            MacroStep::ToggleSelect(r) => ControlEvent::ToggleSelect(resolve(*r)?),
+            MacroStep::ExtendSelection(refs) => ControlEvent::ExtendSelection(
+                refs.iter().map(|r| resolve(*r)).collect::<Option<_>>()?,
+            ),
+            MacroStep::ClearSelection => ControlEvent::ClearSelection,
+            MacroStep::Navigate { dir, times } => ControlEvent::Navigate {
+                dir: *dir,
+                times: *times,
+            },
+            MacroStep::ChangeMode(mode) => ControlEvent::ChangeMode(match mode {
+                ModeStep::Travel => InputMode::Travel,
+                ModeStep::Edit(r) => InputMode::Edit(resolve(*r)?),
+                ModeStep::Link { from, to } => InputMode::Link {
+                    from: resolve(*from)?,
+                    to: resolve(*to)?,
+                },
+                ModeStep::Visual => InputMode::Visual,
+            }),
+            MacroStep::Bulk(op) => ControlEvent::Bulk(op.clone()),
+            MacroStep::Change(change) => ControlEvent::Change(*change),
+            MacroStep::Yank { depth } => ControlEvent::Yank { depth: *depth },
+            MacroStep::Edit(edit) => ControlEvent::Edit(edit.command(resolve, bubbles)?),
+        })
+    }
+
+    fn refs(&self) -> Vec<BubbleRef> {
+        match self {
+            MacroStep::Select(r) | MacroStep::ToggleSelect(r) => vec![*r],
+            MacroStep::ExtendSelection(refs) => refs.clone(),
+            MacroStep::ChangeMode(ModeStep::Edit(r)) => vec![*r],
+            MacroStep::ChangeMode(ModeStep::Link { from, to }) => vec![*from, *to],
+            MacroStep::Edit(edit) => edit.refs(),
+            _ => vec![],
+        }
+    }
+}
+
+/// A recorded sequence of steps
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Macro {
+    pub steps: Vec<MacroStep>,
+    /// Number of bubbles spawned while recording, which playback waits for before the next run
+    pub spawns: usize,
+}
+
+impl Macro {
+    /// True if the macro names the bubble it is played on, so it needs one
+    pub fn uses_start(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| step.refs().contains(&BubbleRef::Start))
+    }
+}
+
+/// Contents of the macros file
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MacroFile {
+    pub macros: BTreeMap<String, Macro>,
+}
+
+/// A macro being recorded
+struct Recording {
+    /// Focused bubble when recording started
+    start: Option<BubbleId>,
+    /// Bubbles spawned since recording started, in order
+    spawned: Vec<BubbleId>,
+    steps: Vec<MacroStep>,
+    /// Endpoints of each connection as of the last frame, for recording edits of connections
+    /// that were removed or reversed before they were recorded
+    edges: Option<HashMap<EdgeId, (BubbleId, BubbleId)>>,
+}
+
+impl Recording {
+    fn to_ref(&self, id: BubbleId) -> BubbleRef {
+        if self.start == Some(id) {
+            BubbleRef::Start
+        } else if let Some(idx) = self.spawned.iter().position(|spawned| *spawned == id) {
+            BubbleRef::Spawned(idx)
+        } else {
+            BubbleRef::Fixed(id)
+        }
+    }
+}
+
+/// A macro being played, see [`play_macros`]
+struct Playback {
+    name: String,
+    recorded: Macro,
+    /// Bubble of each remaining run, the first one is running
+    runs: VecDeque<Option<BubbleId>>,
+    /// Index of the next step of the current run, `None` before the run has selected its bubble
+    next: Option<usize>,
+    /// Bubbles spawned by the current run, in order
+    spawned: Vec<BubbleId>,
+    /// Seconds the current step has waited for a spawned bubble
+    waited: f32,
+    /// Selection before playback, restored afterwards, with the focused bubble last
+    selection: Vec<BubbleId>,
+}
+
+impl Playback {
+    fn resolve(&self, target: Option<BubbleId>, r: BubbleRef) -> Option<BubbleId> {
+        match r {
+            BubbleRef::Start => target,
+            BubbleRef::Spawned(idx) => self.spawned.get(idx).copied(),
+            BubbleRef::Fixed(id) => Some(id),
+        }
+    }
+}
+
+/// Macros loaded from [`Macros::path`], and the recording and playback in progress
+#[derive(Resource)]
+pub struct Macros {
+    pub path: PathBuf,
+    file: MacroFile,
+    /// Macro played by [`Action::PlayMacro`](super::Action::PlayMacro)
+    pub selected: Option<String>,
+    /// Modification time of the file when it was last loaded
+    loaded_at: Option<SystemTime>,
+    reload_timer: Timer,
+    recording: Option<Recording>,
+    playback: Option<Playback>,
+    /// Error from the last load, save, recording or playback, shown in the UI
+    pub error: Option<String>,
+    /// Name typed in the UI for the next recording
+    pub name_input: String,
+    /// Runs per selected bubble when playing from the UI
+    pub count: usize,
+}
+
+impl Default for Macros {
+    fn default() -> Self {
+        Self::new(DEFAULT_MACROS_PATH)
+    }
+}
+
+impl Macros {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: MacroFile::default(),
+            selected: None,
+            loaded_at: None,
+            reload_timer: Timer::new(RELOAD_INTERVAL, TimerMode::Repeating),
+            recording: None,
+            playback: None,
+            error: None,
+            name_input: String::new(),
+            count: 1,
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.file.macros.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Macro> {
+        self.file.macros.get(name)
+    }
+
+    /// Number of steps recorded so far, or `None` if not recording
+    pub fn recorded_steps(&self) -> Option<usize> {
+        self.recording
+            .as_ref()
+            .map(|recording| recording.steps.len())
+    }
+
+    /// Name of the macro being played, if any
+    pub fn playing(&self) -> Option<&str> {
+        self.playback
+            .as_ref()
+            .map(|playback| playback.name.as_str())
+    }
+
+    /// Starts recording, relative to the `focused` bubble
+    pub fn start_recording(&mut self, focused: Option<BubbleId>) -> Result<(), MacroError> {
+        if self.recording.is_some() {
+            return Err(MacroError::Recording);
+        }
+        if self.playback.is_some() {
+            return Err(MacroError::Playing);
+        }
+        self.recording = Some(Recording {
+            start: focused,
+            spawned: Vec::new(),
+            steps: Vec::new(),
+            edges: None,
+        });
+        Ok(())
+    }
+
+    /// Stops recording and stores the macro as `name`, replacing any previous one, and writes
+    /// the file
+    ///
+    /// The recording is dropped without saving if it names a [`BubbleRef::Fixed`] bubble.
+    pub fn stop_recording(&mut self, name: &str) -> Result<(), MacroError> {
+        let recording = self.recording.take().ok_or(MacroError::NotRecording)?;
+        let fixed = recording
+            .steps
+            .iter()
+            .flat_map(MacroStep::refs)
+            .find_map(|r| match r {
+                BubbleRef::Fixed(id) => Some(id),
+                _ => None,
+            });
+        if let Some(id) = fixed {
+            return Err(MacroError::FixedBubble {
+                name: name.to_string(),
+                id,
+            });
+        }
+        let recorded = Macro {
+            steps: recording.steps,
+            spawns: recording.spawned.len(),
+        };
+        self.file.macros.insert(name.to_string(), recorded);
+        self.selected = Some(name.to_string());
+        self.write()
+    }
+
+    pub fn cancel_recording(&mut self) {
+        self.recording = None;
+    }
+
+    /// Starts recording, or stops it and stores the macro under the name typed in the UI
+    pub fn toggle_recording(&mut self, focused: Option<BubbleId>) -> Result<(), MacroError> {
+        if self.recording.is_none() {
+            return self.start_recording(focused);
+        }
+        let name = match self.name_input.trim() {
+            "" => format!("macro {}", self.file.macros.len() + 1),
+            name => name.to_string(),
+        };
+        self.stop_recording(&name)
+    }
+
+    /// Plays the macro `count` times on each selected bubble, or on nothing if none is selected
+    pub fn play(
+        &mut self,
+        name: &str,
+        count: usize,
+        control: &CursorControl,
+    ) -> Result<(), MacroError> {
+        if self.recording.is_some() {
+            return Err(MacroError::Recording);
+        }
+        if self.playback.is_some() {
+            return Err(MacroError::Playing);
+        }
+        let recorded = self
+            .get(name)
+            .ok_or_else(|| MacroError::NotFound(name.to_string()))?
+            .clone();
+        let targets: Vec<Option<BubbleId>> = if control.selected.is_empty() {
+            if recorded.uses_start() {
+                return Err(MacroError::NoTarget(name.to_string()));
+            }
+            vec![None]
+        } else {
+            control.selected.iter().copied().map(Some).collect()
+        };
+
+        let mut selection: Vec<BubbleId> = control
+            .selected
+            .iter()
+            .copied()
+            .filter(|id| Some(*id) != control.focused)
+            .collect();
+        selection.extend(control.focused);
+        self.playback = Some(Playback {
+            name: name.to_string(),
+            recorded,
+            runs: targets
+                .into_iter()
+                .flat_map(|target| std::iter::repeat_n(target, count))
+                .collect(),
+            next: None,
+            spawned: Vec::new(),
+            waited: 0.,
+            selection,
+        });
+        Ok(())
+    }
+
+    /// Plays the [`Macros::selected`] macro, see [`Macros::play`]
+    pub fn play_selected(
+        &mut self,
+        count: usize,
+        control: &CursorControl,
+    ) -> Result<(), MacroError> {
+        let name = self.selected.clone().ok_or(MacroError::NoneSelected)?;
+        self.play(&name, count, control)
+    }
+
+    /// Deletes the macro and writes the file
+    pub fn remove(&mut self, name: &str) -> Result<(), MacroError> {
+        self.file
+            .macros
+            .remove(name)
+            .ok_or_else(|| MacroError::NotFound(name.to_string()))?;
+        if self.selected.as_deref() == Some(name) {
+            self.selected = None;
+        }
+        self.write()
+    }
+
+    /// Writes all macros to the file, creating its directory if necessary
+    pub fn write(&mut self) -> Result<(), MacroError> {
+        let io_err = |source| MacroError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let text = ron::ser::to_string_pretty(&self.file, ron::ser::PrettyConfig::default())?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        fs::write(&self.path, text).map_err(io_err)?;
+        // Do not reload what was just written
+        self.loaded_at = modified_time(&self.path);
+        Ok(())
+    }
+
+    /// Reloads the file if it has changed since it was last loaded
+    ///
+    /// Returns true if the macros were reloaded. A missing file keeps the current macros.
+    pub fn reload_if_changed(&mut self) -> Result<bool, MacroError> {
+        let Some(modified) = modified_time(&self.path) else {
+            return Ok(false);
+        };
+        if self.loaded_at == Some(modified) {
+            return Ok(false);
+        }
+        // Only try each version of the file once, even if it fails to parse
+        self.loaded_at = Some(modified);
+
+        let text = fs::read_to_string(&self.path).map_err(|source| MacroError::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        self.file = ron::from_str(&text).map_err(|source| MacroError::Parse {
+            path: self.path.clone(),
+            source,
+        })?;
+        Ok(true)
+    }
+}
+
+/// Hot-reloads the macros file
+pub fn reload_macros(time: Res<Time>, mut macros: ResMut<Macros>) {
+    if !macros.reload_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    match macros.reload_if_changed() {
+        Ok(true) => macros.error = None,
+        Ok(false) => {}
+        Err(e) => macros.error = Some(e.to_string()),
+    }
+}
+
+/// Adds the control events and spawned bubbles of this frame to the recording, if any
+pub fn record_macros(
+    mut control_events: EventReader<ControlEvent>,
+    mut bubble_events: EventReader<BubbleEvent>,
+    mut macros: ResMut<Macros>,
+    bubbles: Res<Bubbles>,
+) {
+    let Some(recording) = macros.recording.as_mut() else {
+        control_events.clear();
+        bubble_events.clear();
+        return;
+    };
+    for ev in bubble_events.iter() {
+        if let BubbleEvent::Spawned { id, .. } = ev {
+            recording.spawned.push(*id);
+        }
+    }
+    let edges = recording
+        .edges
+        .take()
+        .unwrap_or_else(|| edge_endpoints(&bubbles));
+    for ev in control_events.iter() {
+        if let Some(step) = MacroStep::record(ev, |id| recording.to_ref(id), &edges) {
+            recording.steps.push(step);
+        }
+    }
+    recording.edges = Some(if bubbles.is_changed() {
+        edge_endpoints(&bubbles)
+    } else {
+        edges
+    });
+}
+
+fn edge_endpoints(bubbles: &Bubbles) -> HashMap<EdgeId, (BubbleId, BubbleId)> {
+    bubbles
+        .edge_ids()
+        .into_iter()
+        .filter_map(|edge| Some((edge, bubbles.edge_endpoints(edge).ok()?)))
+        .collect()
+}
+
+/// Sends the steps of the macro being played, if any
+///
+/// Each run selects its bubble and sends the steps in order. A step naming a bubble spawned by
+/// the run waits until it is spawned, and the next run starts once all bubbles of this one are
+/// spawned. The selection from before playback is restored at the end.
+///
+/// Runs before [`handle_view_event`](crate::camera::handle_view_event), so the events sent in a
+/// frame are handled in the same frame. Changes and yanks are applied after all selection events
+/// of the frame, so the steps after one are sent in the next frame, once the change was applied to
+/// the bubble focused at the time. Edits are applied after the frame's events too, so the steps
+/// after one can name the bubbles and connections it makes. For the same reason, each run starts
+/// in a new frame.
+pub fn play_macros(
+    time: Res<Time>,
+    mut macros: ResMut<Macros>,
+    mut bubble_events: EventReader<BubbleEvent>,
+    mut control_events: EventWriter<ControlEvent>,
+    bubbles: Res<Bubbles>,
+) {
+    let Some(playback) = macros.playback.as_mut() else {
+        bubble_events.clear();
+        return;
+    };
+    for ev in bubble_events.iter() {
+        if let BubbleEvent::Spawned { id, .. } = ev {
+            playback.spawned.push(*id);
+        }
+    }
+
+    while let Some(target) = playback.runs.front().copied() {
+        let Some(next) = playback.next else {
+            if let Some(target) = target {
+                control_events.send(ControlEvent::Select(target));
+            }
+            playback.next = Some(0);
+            continue;
+        };
+        match playback.recorded.steps.get(next) {
+            Some(step) => {
+                if let Some(ev) = step.event(|r| playback.resolve(target, r), &bubbles) {
+                    let applied_later = matches!(
+                        ev,
+                        ControlEvent::Change(_) | ControlEvent::Yank { .. } | ControlEvent::Edit(_)
+                    );
+                    control_events.send(ev);
+                    playback.next = Some(next + 1);
+                    playback.waited = 0.;
+                    if applied_later {
+                        return;
+                    }
+                    continue;
+                }
+            }
+            // Keep the bubbles of this run apart from those of the next one
+            None if playback.spawned.len() < playback.recorded.spawns => {}
+            None => {
+                playback.runs.pop_front();
+                playback.next = None;
+                playback.spawned.clear();
+                playback.waited = 0.;
+                return;
+            }
+        }
+
+        // Wait for the bubbles spawned by the run
+        playback.waited += time.delta_seconds();
+        if playback.waited > MAX_WAIT {
+            let error = MacroError::Stalled(playback.name.clone());
+            warn!("{error}");
+            macros.error = Some(error.to_string());
+            macros.playback = None;
+        }
+        return;
+    }
+
+    if let Some(playback) = macros.playback.take() {
+        control_events.send(ControlEvent::ClearSelection);
+        if !playback.selection.is_empty() {
+            control_events.send(ControlEvent::ExtendSelection(playback.selection));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera::{handle_view_event, CameraPlugin},
+        headless_app,
+    };
+
+    fn app(macros_path: PathBuf) -> App {
+        let mut app = headless_app();
+        app.add_plugins(CameraPlugin)
+            .insert_resource(Macros::new(macros_path))
+            .add_systems(
+                Update,
+                (
+                    record_macros.after(crate::bubbles::apply_bubble_commands),
+                    play_macros.before(handle_view_event),
+                ),
+            );
+        app
+    }
+
+    #[test]
+    fn edits_from_the_mouse_are_recorded_relative_to_the_start() {
+        let path = std::env::temp_dir().join(format!("zeal-macros-{}.ron", std::process::id()));
+        let mut app = app(path.clone());
+        app.world.send_event(BubbleCommand::SpawnOrphan(Vec2::ZERO));
+        app.world
+            .send_event(BubbleCommand::SpawnOrphan(Vec2::X * 300.));
+        app.update();
+
+        app.world
+            .resource_mut::<Macros>()
+            .start_recording(Some(0))
+            .unwrap();
+        app.world
+            .send_event(ControlEvent::Edit(BubbleCommand::SpawnChild(0)));
+        app.update();
+        app.update();
+        // Reversing removes the edge before it is recorded, so it is recorded by its endpoints
+        let edge = app.world.resource::<Bubbles>().find_edge(2, 0).unwrap();
+        app.world.send_event_batch([
+            ControlEvent::Edit(BubbleCommand::SetEdgeKind(edge, EdgeKind::Reference)),
+            ControlEvent::Edit(BubbleCommand::ReverseEdge(edge)),
+        ]);
+        app.update();
+        app.world
+            .resource_mut::<Macros>()
+            .stop_recording("child")
+            .unwrap();
+
+        let mut control = CursorControl::default();
+        control.select(1);
+        app.world
+            .resource_mut::<Macros>()
+            .play("child", 1, &control)
+            .unwrap();
+        for _ in 0..10 {
+            app.update();
+        }
+        fs::remove_file(path).unwrap();
+
+        let bubbles = app.world.resource::<Bubbles>();
+        let spawned = bubbles.neighbors(1).unwrap();
+        assert_eq!(spawned.len(), 1);
+        let edge = bubbles.find_edge(1, spawned[0]).unwrap();
+        assert_eq!(bubbles.edge_kind(edge).unwrap(), EdgeKind::Reference);
+        assert!(app.world.resource::<Macros>().error.is_none());
+    }
+
+    #[test]
+    fn recordings_naming_other_bubbles_are_not_saved() {
+        let path = std::env::temp_dir().join(format!("zeal-fixed-{}.ron", std::process::id()));
+        let mut app = app(path.clone());
+        app.world.send_event(BubbleCommand::SpawnOrphan(Vec2::ZERO));
+        app.world
+            .send_event(BubbleCommand::SpawnOrphan(Vec2::X * 300.));
+        app.update();
+
+        let mut macros = app.world.resource_mut::<Macros>();
+        macros.start_recording(Some(0)).unwrap();
+        app.world.send_event(ControlEvent::Select(1));
+        app.update();
+
+        let mut macros = app.world.resource_mut::<Macros>();
+        let result = macros.stop_recording("other");
+        assert!(
+            matches!(result, Err(MacroError::FixedBubble { id: 1, .. })),
+            "{result:?}"
+        );
+        assert!(macros.get("other").is_none());
+        assert!(macros.recorded_steps().is_none());
+        assert!(!path.exists());
+    }
+}
@@ -1,14 +1,105 @@
+mod action;
+mod box_select;
+mod command_palette;
+mod context_menu;
+mod edge_drag;
+mod edit_mode;
+mod gamepad;
+mod keymap;
+mod link_mode;
+mod macros;
+mod picking;
+mod touch;
 mod travel_mode;
+mod vim;
+mod visual_mode;
+
+pub use action::{handle_actions, Action, ActionEvent, Direction};
+pub use box_select::{handle_box_select, BoxSelect};
+pub use command_palette::CommandPalette;
+pub use context_menu::{handle_context_menu, ContextMenu, MenuTarget};
+pub use edge_drag::{handle_edge_drag, EdgeDrag};
+pub use gamepad::{
+    button_action, connect_event, handle_gamepad_buttons, handle_gamepad_sticks, GamepadConfig,
+    GamepadPlugin, BUTTONS,
+};
+pub use keymap::{
+    dispatch_keys, format_sequence, reload_keymap, Chord, Keymap, KeymapFile, PendingKeys,
+    PixelScroll,
+};
+pub use macros::{
+    play_macros, record_macros, reload_macros, BubbleRef, EditStep, Macro, MacroError, MacroFile,
+    MacroStep, Macros, ModeStep,
+};
+pub use touch::handle_touch;
+pub use vim::{apply_changes, ChangeError, Register};
+pub use visual_mode::PALETTE;
+
+pub(crate) use self::picking::NavTargets;
+
+use self::picking::{bubble_at, CursorWorld};
 
 use crate::{
-    camera::ControlEvent,
-    cursor_control::{CursorControl, InputMode},
+    bubbles::{apply_bubble_commands, BubbleCommand, BubbleEvent},
+    camera::{handle_view_event, ControlEvent},
+    cursor_control::CursorControl,
+    session::LiveInput,
+    GraphBubble,
 };
 use bevy::{
-    input::mouse::{MouseMotion, MouseWheel},
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
     prelude::*,
 };
-use bevy_egui::EguiContexts;
+use bevy_egui::{EguiContexts, EguiPlugin};
+
+/// Turns mouse, keyboard, touch and gamepad input into [`ControlEvent`]s
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        // Egui is needed for checking whether it wants the input
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        if !app.is_plugin_added::<GamepadPlugin>() {
+            app.add_plugins(GamepadPlugin);
+        }
+        app.add_event::<ControlEvent>()
+            .add_event::<BubbleCommand>()
+            .add_event::<ActionEvent>()
+            .add_event::<BubbleEvent>()
+            .init_resource::<CursorControl>()
+            .init_resource::<Keymap>()
+            .init_resource::<PendingKeys>()
+            .init_resource::<EdgeDrag>()
+            .init_resource::<BoxSelect>()
+            .init_resource::<ContextMenu>()
+            .init_resource::<CommandPalette>()
+            .init_resource::<Register>()
+            .init_resource::<Macros>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        handle_edge_drag,
+                        handle_box_select,
+                        handle_click_select,
+                        handle_mouse,
+                        handle_touch,
+                    )
+                        .chain(),
+                    (reload_keymap, dispatch_keys, handle_actions).chain(),
+                    travel_mode::handle_pan,
+                    handle_context_menu,
+                    reload_macros,
+                    record_macros.after(apply_bubble_commands),
+                    play_macros.before(handle_view_event),
+                )
+                    .in_set(LiveInput),
+            )
+            .add_systems(Update, apply_changes.after(handle_view_event));
+    }
+}
 
 /// # Documentation
 ///
@@ -20,34 +111,80 @@ pub fn handle_mouse(
     mut mouse_motions: EventReader<MouseMotion>,
     mut mouse_wheels: EventReader<MouseWheel>,
     mut view_evs: EventWriter<ControlEvent>,
+    (drag, select, keymap): (Res<EdgeDrag>, Res<BoxSelect>, Res<Keymap>),
 ) {
     // If the cursor is on top of egui, do not create control events
     if egui_is_hovered(contexts) {
         return;
     }
 
+    // Dragging an edge or a selection uses the left mouse button instead of panning
+    if drag.is_active() || select.is_active() {
+        mouse_motions.clear();
+    }
+
     handle_drag_events(btn_state, &mut mouse_motions, &mut view_evs);
 
     // Handle scroll events
-    handle_wheel_events(&mut mouse_wheels, &mut view_evs);
+    handle_wheel_events(&mut mouse_wheels, keymap.pixel_scroll(), &mut view_evs);
 }
 
-fn egui_is_hovered(mut contexts: EguiContexts) -> bool {
+/// Selects the bubble under the cursor on left click
+///
+/// Shift-clicks are left to [`handle_box_select`], which toggles the bubble instead.
+pub fn handle_click_select(
+    contexts: EguiContexts,
+    btn_state: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: CursorWorld,
+    bubbles: Query<(&GraphBubble, &Transform)>,
+    (drag, select): (Res<EdgeDrag>, Res<BoxSelect>),
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !btn_state.just_pressed(MouseButton::Left)
+        || shift
+        || drag.is_active()
+        || select.is_active()
+        || egui_is_hovered(contexts)
+    {
+        return;
+    }
+    if let Some(id) = cursor
+        .position()
+        .and_then(|pos| bubble_at(pos, bubbles.iter()))
+    {
+        control_events.send(ControlEvent::Select(id));
+    }
+}
+
+pub(crate) fn egui_is_hovered(mut contexts: EguiContexts) -> bool {
     let ctx = contexts.ctx_mut();
     ctx.is_pointer_over_area()
 }
 
+/// Scrolled pixels that zoom as much as one line, with [`PixelScroll::Zoom`]
+const PIXELS_PER_LINE: f32 = 50.;
+
 fn handle_wheel_events(
     mouse_wheels: &mut EventReader<MouseWheel>,
+    pixel_scroll: PixelScroll,
     view_evs: &mut EventWriter<ControlEvent>,
 ) {
     for ev in mouse_wheels.iter() {
-        let MouseWheel { unit: _unit, y, .. } = ev;
-        /*match unit {
-            MouseScrollUnit::Line => todo!(),
-            MouseScrollUnit::Pixel => todo!(),
-        }*/
-        view_evs.send(ControlEvent::ZoomIn(*y));
+        let MouseWheel { unit, x, y, .. } = ev;
+        match (unit, pixel_scroll) {
+            // Mouse wheels scroll in lines and zoom
+            (MouseScrollUnit::Line, _) => view_evs.send(ControlEvent::ZoomIn(*y)),
+            // Trackpads scroll in pixels with two fingers and pan, like dragging, while pinching
+            // zooms, see `handle_touch`
+            (MouseScrollUnit::Pixel, PixelScroll::Pan) => {
+                view_evs.send(ControlEvent::Pan(Vec2::new(-x, -y)))
+            }
+            (MouseScrollUnit::Pixel, PixelScroll::Zoom) => {
+                view_evs.send(ControlEvent::ZoomIn(y / PIXELS_PER_LINE))
+            }
+        }
     }
 }
 
@@ -63,26 +200,3 @@ fn handle_drag_events(
         }
     }
 }
-
-/// # Documentation
-///
-/// Input handling: https://bevy-cheatbook.github.io/builtins.html#input-handling-resources
-/// Input event list: https://bevy-cheatbook.github.io/builtins.html#input-events
-///
-/// # Arguments
-///
-/// * `skeyboard` - Keyboard state
-pub fn handle_keyboard(
-    contexts: EguiContexts,
-    time: Res<Time>,
-    skeyboard: Res<Input<KeyCode>>,
-    view_moves: EventWriter<ControlEvent>,
-    control: Res<CursorControl>,
-) {
-    match control.input_mode {
-        InputMode::Travel => {
-            travel_mode::handle_keyboard(contexts, time, control, skeyboard, view_moves)
-        }
-        InputMode::Edit(_) => todo!(),
-    }
-}
@@ -0,0 +1,107 @@
+//! Finding what is under the cursor or in a direction
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+
+use crate::{
+    bubbles::{BubbleId, Bubbles, BUBBLE_SIZE},
+    camera::MainCamera,
+    GraphBubble,
+};
+
+/// Position of the cursor as seen through the [`MainCamera`]
+#[derive(SystemParam)]
+pub struct CursorWorld<'w, 's> {
+    windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCamera>>,
+}
+
+impl CursorWorld<'_, '_> {
+    /// Returns the position of the cursor in world coordinates, or `None` if it is outside the
+    /// window
+    pub fn position(&self) -> Option<Vec2> {
+        self.to_world(self.screen_position()?)
+    }
+
+    /// Converts a point in logical pixels from the top left of the window to world coordinates
+    pub fn to_world(&self, screen_pos: Vec2) -> Option<Vec2> {
+        let (camera, gtfm) = self.camera.get_single().ok()?;
+        camera.viewport_to_world_2d(gtfm, screen_pos)
+    }
+
+    /// Returns the position of the cursor in logical pixels from the top left of the window
+    pub fn screen_position(&self) -> Option<Vec2> {
+        self.windows.get_single().ok()?.cursor_position()
+    }
+}
+
+/// Returns the bubble whose ellipse contains `pos`, preferring the closest one if they overlap
+pub(crate) fn bubble_at<'a>(
+    pos: Vec2,
+    bubbles: impl Iterator<Item = (&'a GraphBubble, &'a Transform)>,
+) -> Option<BubbleId> {
+    bubbles
+        .filter_map(|(id, tfm)| {
+            // Distance in units of the ellipse radii, so 1 is on the edge
+            let d = ((pos - tfm.translation.truncate()) / BUBBLE_SIZE).length();
+            (d <= 1.).then_some((id.0, d))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(id, _)| id)
+}
+
+/// Returns the edge whose line passes closest to `pos`, if any is within a few pixels
+///
+/// `edges` yields each edge with the positions of its endpoints.
+pub(crate) fn edge_at<T>(pos: Vec2, edges: impl Iterator<Item = (T, Vec2, Vec2)>) -> Option<T> {
+    // Half the width of the drawn line, with some slack
+    const MAX_DISTANCE: f32 = 8.;
+    edges
+        .map(|(edge, from, to)| (edge, distance_to_segment(pos, from, to)))
+        .filter(|(_, d)| *d <= MAX_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(edge, _)| edge)
+}
+
+fn distance_to_segment(pos: Vec2, from: Vec2, to: Vec2) -> f32 {
+    let line = to - from;
+    let len_sq = line.length_squared();
+    if len_sq == 0. {
+        return pos.distance(from);
+    }
+    let t = ((pos - from).dot(line) / len_sq).clamp(0., 1.);
+    pos.distance(from + line * t)
+}
+
+/// Returns the closest bubble roughly in direction `dir` from `origin`
+///
+/// Only bubbles within 60 degrees of `dir` are considered.
+fn bubble_in_direction<'a>(
+    origin: Vec2,
+    dir: Vec2,
+    bubbles: impl Iterator<Item = (&'a GraphBubble, &'a Transform)>,
+) -> Option<BubbleId> {
+    const MIN_COS: f32 = 0.5;
+    let dir = dir.normalize_or_zero();
+    bubbles
+        .filter_map(|(id, tfm)| {
+            let offset = tfm.translation.truncate() - origin;
+            let dist = offset.length();
+            (dist > 0. && offset.dot(dir) / dist >= MIN_COS).then_some((id.0, dist))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(id, _)| id)
+}
+
+/// Bubbles that can be navigated to with the arrow keys
+#[derive(SystemParam)]
+pub struct NavTargets<'w, 's> {
+    bubbles: Res<'w, Bubbles>,
+    transforms: Query<'w, 's, (&'static GraphBubble, &'static Transform)>,
+}
+
+impl NavTargets<'_, '_> {
+    /// Returns the closest bubble roughly in direction `dir` from bubble `from`
+    pub(crate) fn in_direction(&self, from: BubbleId, dir: Vec2) -> Option<BubbleId> {
+        let (_, tfm) = self.transforms.get(self.bubbles.entity(from)?).ok()?;
+        bubble_in_direction(tfm.translation.truncate(), dir, self.transforms.iter())
+    }
+}
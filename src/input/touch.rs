@@ -0,0 +1,109 @@
+//! Touch screen and trackpad gestures
+//!
+//! Two fingers pan and pinch-zoom the view, holding one finger in place opens the context menu,
+//! and the trackpad pinch zooms around the cursor. Everything is sent as [`ControlEvent`]s.
+//!
+//! Scrolling with two fingers on a trackpad arrives as pixel-based mouse wheel events, which
+//! `handle_mouse` turns into pans, while line-based ones from a mouse wheel zoom.
+use bevy::{
+    input::{touch::Touch, touchpad::TouchpadMagnify},
+    prelude::*,
+};
+use bevy_egui::EguiContexts;
+
+use super::{egui_is_hovered, picking::CursorWorld};
+use crate::camera::ControlEvent;
+
+/// Seconds a finger has to be held for a long press
+const LONG_PRESS_SECS: f32 = 0.5;
+/// How far a finger can move, in logical pixels, and still make a long press
+const LONG_PRESS_SLOP: f32 = 10.;
+
+/// Gestures in progress, see [`handle_touch`]
+#[derive(Default)]
+pub struct TouchGestures {
+    /// Center and finger distance of the two-finger gesture when it was last handled
+    pinch: Option<(Vec2, f32)>,
+    press: Option<LongPress>,
+}
+
+/// A single finger that may become a long press
+struct LongPress {
+    touch: u64,
+    held: f32,
+    /// The finger moved too far, or the press already opened the menu
+    done: bool,
+}
+
+/// Turns touch and trackpad gestures into [`ControlEvent`]s, unless they are on top of egui
+pub fn handle_touch(
+    contexts: EguiContexts,
+    time: Res<Time>,
+    touches: Res<Touches>,
+    mut magnify: EventReader<TouchpadMagnify>,
+    cursor: CursorWorld,
+    mut gestures: Local<TouchGestures>,
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    if egui_is_hovered(contexts) {
+        magnify.clear();
+        *gestures = TouchGestures::default();
+        return;
+    }
+
+    if let Some(anchor) = cursor.screen_position() {
+        for TouchpadMagnify(delta) in magnify.iter() {
+            control_events.send(ControlEvent::ZoomAt {
+                factor: 1. + delta,
+                anchor,
+            });
+        }
+    }
+
+    let active: Vec<&Touch> = touches.iter().collect();
+    match active.as_slice() {
+        [a, b] => {
+            gestures.press = None;
+            let center = (a.position() + b.position()) / 2.;
+            let distance = a.position().distance(b.position());
+            if let Some((last_center, last_distance)) = gestures.pinch {
+                let pan = center - last_center;
+                if pan != Vec2::ZERO {
+                    // Like dragging with the mouse, the view follows the fingers
+                    control_events.send(ControlEvent::Pan(-pan));
+                }
+                if last_distance > 0. && distance > 0. && distance != last_distance {
+                    control_events.send(ControlEvent::ZoomAt {
+                        factor: distance / last_distance,
+                        anchor: center,
+                    });
+                }
+            }
+            gestures.pinch = Some((center, distance));
+        }
+        [touch] => {
+            gestures.pinch = None;
+            // Only a finger that touched down alone can long press, not one left over from a
+            // two-finger gesture
+            if touches.just_pressed(touch.id()) {
+                gestures.press = Some(LongPress {
+                    touch: touch.id(),
+                    held: 0.,
+                    done: false,
+                });
+            }
+            let Some(press) = gestures.press.as_mut().filter(|p| p.touch == touch.id()) else {
+                return;
+            };
+            press.held += time.delta_seconds();
+            if touch.distance().length() > LONG_PRESS_SLOP {
+                press.done = true;
+            }
+            if !press.done && press.held >= LONG_PRESS_SECS {
+                press.done = true;
+                control_events.send(ControlEvent::OpenContextMenu(touch.position()));
+            }
+        }
+        _ => *gestures = TouchGestures::default(),
+    }
+}
@@ -1,79 +1,114 @@
 use bevy::{prelude::*, time::Time};
 use bevy_egui::EguiContexts;
 
+use super::{Action, ActionEvent, Direction, Keymap, Register};
 use crate::{
+    bubbles::BubbleCommand,
     camera::ControlEvent,
-    cursor_control::{CursorControl, InputMode},
+    cursor_control::{Change, CursorControl, InputMode},
 };
 
-/// # Documentation
-///
-/// Input handling: https://bevy-cheatbook.github.io/builtins.html#input-handling-resources
-/// Input event list: https://bevy-cheatbook.github.io/builtins.html#input-events
-///
-/// # Arguments
+/// Change of the camera scale per [`Action::ZoomIn`]
+const ZOOM_STEP: f32 = 0.25;
+
+/// Zooms, moves the focus, edits the graph around the focused bubble or switches to another mode
+/// starting from it
 ///
-/// * `skeyboard` - Keyboard state
-pub fn handle_keyboard(
-    mut contexts: EguiContexts,
-    time: Res<Time>,
-    control: Res<CursorControl>,
-    keyboard_state: Res<Input<KeyCode>>,
-    mut control_events: EventWriter<ControlEvent>,
+/// Edits are sent as [`ControlEvent::Change`]s, see [`vim`](super::vim).
+pub fn handle_action(
+    ActionEvent { action, count }: ActionEvent,
+    (control, register): (&CursorControl, &Register),
+    control_events: &mut EventWriter<ControlEvent>,
+    bubble_commands: &mut EventWriter<BubbleCommand>,
 ) {
-    // If egui wants keyboard input, do not create view events
-    // TODO: this is not ideal for all cases -> refactor later
-    let ctx = contexts.ctx_mut();
-    if ctx.wants_keyboard_input() {
-        return;
-    }
-
-    handle_arrow_keys(&keyboard_state, &time, &mut control_events);
-
-    if keyboard_state.pressed(KeyCode::I) {
-        if let Some(bubble_id) = control.selected {
-            // Change from travel mode to edit mode
-            control_events.send(ControlEvent::ChangeMode(InputMode::Edit(bubble_id)));
+    let times = count.unwrap_or(1);
+    let change = match action {
+        Action::ZoomIn => {
+            control_events.send(ControlEvent::ZoomIn(ZOOM_STEP));
+            return;
         }
-    }
+        Action::ZoomOut => {
+            control_events.send(ControlEvent::ZoomIn(-ZOOM_STEP));
+            return;
+        }
+        Action::ClearSelection => {
+            control_events.send(ControlEvent::ClearSelection);
+            return;
+        }
+        Action::SettleLayout => {
+            bubble_commands.send(BubbleCommand::Settle);
+            return;
+        }
+        Action::YankSubtree => {
+            control_events.send(ControlEvent::Yank { depth: count });
+            return;
+        }
+        Action::AddChild => Change::NewChild { count: times },
+        Action::NewSibling => Change::NewSibling { count: times },
+        Action::DeleteFocused => Change::DeleteBubble,
+        Action::DeleteSubtree => Change::DeleteSubtree { depth: count },
+        Action::Paste => Change::Paste { count: times },
+        Action::RepeatChange => match register.last_change {
+            Some(last) => count.map_or(last, |count| last.with_count(count)),
+            None => return,
+        },
+        _ => return switch_from_focused(action, times, control, control_events),
+    };
+    control_events.send(ControlEvent::Change(change));
 }
 
-fn handle_arrow_keys(
-    keyboard_state: &Input<KeyCode>,
-    time: &Time,
+/// Moves the focus or switches mode, starting from the focused bubble
+fn switch_from_focused(
+    action: Action,
+    times: usize,
+    control: &CursorControl,
     control_events: &mut EventWriter<ControlEvent>,
 ) {
-    if let Some(v) = arrow_keys_to_vec(&keyboard_state) {
-        const KB_MOVE_PX_PER_SEC: f32 = 500.;
-        let pan_event = ControlEvent::Pan(v * KB_MOVE_PX_PER_SEC * time.delta_seconds());
-        control_events.send(pan_event);
-    }
-}
-
-/// Convert arrow keys (left, right, up down) into a normalized vector such as `(1., 0.)` for right arrow or `(-1.,
-/// -1.)` for up and left at the same time
-fn arrow_keys_to_vec(skeyboard: &Input<KeyCode>) -> Option<Vec2> {
-    let left = skeyboard.pressed(KeyCode::Left);
-    let right = skeyboard.pressed(KeyCode::Right);
-    let up = skeyboard.pressed(KeyCode::Up);
-    let down = skeyboard.pressed(KeyCode::Down);
-
-    let dx = match (left, right) {
-        (true, false) => Some(-1.),
-        (false, true) => Some(1.),
-        _ => None,
+    let Some(focused) = control.focused else {
+        return;
     };
-    let dy = match (up, down) {
-        (true, false) => Some(-1.),
-        (false, true) => Some(1.),
-        _ => None,
+    let mode = match action {
+        Action::Edit => InputMode::Edit(focused),
+        Action::Link => InputMode::Link {
+            from: focused,
+            to: focused,
+        },
+        Action::Visual => InputMode::Visual,
+        Action::Navigate(dir) => {
+            control_events.send(ControlEvent::Navigate { dir, times });
+            return;
+        }
+        // Panning is handled by `handle_pan` for as long as the key is held
+        _ => return,
     };
+    control_events.send(ControlEvent::ChangeMode(mode));
+}
 
-    if dx.is_none() && dy.is_none() {
-        return None;
+/// Pans the view while keys bound to [`Action::Pan`] are held, at [`Keymap::pan_speed`]
+pub fn handle_pan(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    keyboard_state: Res<Input<KeyCode>>,
+    keymap: Res<Keymap>,
+    control: Res<CursorControl>,
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    // If egui wants keyboard input, do not create view events
+    if !matches!(control.input_mode, InputMode::Travel) || contexts.ctx_mut().wants_keyboard_input()
+    {
+        return;
     }
 
-    let x = dx.unwrap_or(0.);
-    let y = dy.unwrap_or(0.);
-    Some(Vec2::new(x, y))
+    let dir: Vec2 = Direction::ALL
+        .into_iter()
+        .filter(|dir| keymap.is_held("travel", Action::Pan(*dir), &keyboard_state))
+        .map(Direction::vec)
+        .sum();
+    if dir != Vec2::ZERO {
+        // Pans are in screen coordinates, where y points down
+        let v = Vec2::new(dir.x, -dir.y);
+        control_events.send(ControlEvent::Pan(
+            v * keymap.pan_speed() * time.delta_seconds(),
+        ));
+    }
 }
@@ -0,0 +1,139 @@
+//! Vim-style editing in travel mode
+//!
+//! Counts such as the `3` in `3j` and sequences such as `dd` are parsed by the
+//! [`Keymap`](super::Keymap). The resulting [`Change`]s and yanks are sent as [`ControlEvent`]s,
+//! so they are listed in the control history like any other input, and applied here.
+use bevy::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    bubbles::{
+        BubbleColor, BubbleCommand, BubbleContent, BubbleGraphError, BubbleId, Bubbles,
+        SubtreeCopy, BUBBLE_SIZE,
+    },
+    camera::ControlEvent,
+    cursor_control::{Change, CursorControl},
+};
+
+#[derive(Error, Debug)]
+pub enum ChangeError {
+    #[error("no bubble is focused")]
+    NoFocus,
+    #[error("nothing has been yanked")]
+    NothingYanked,
+    #[error(transparent)]
+    Graph(#[from] BubbleGraphError),
+}
+
+/// Yanked bubbles and the last change, for pasting and repeating
+#[derive(Resource, Default)]
+pub struct Register {
+    /// Copied by [`ControlEvent::Yank`] and spawned by [`Change::Paste`]
+    pub yanked: Option<SubtreeCopy>,
+    /// The most recent successful change, repeated by [`Action::RepeatChange`](super::Action::RepeatChange)
+    pub last_change: Option<Change>,
+}
+
+type StyleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static BubbleContent,
+        &'static BubbleColor,
+        &'static Transform,
+    ),
+>;
+
+/// Applies the [`ControlEvent::Change`]s and [`ControlEvent::Yank`]s to the focused bubble
+///
+/// Changes that fail are logged and skipped.
+pub fn apply_changes(
+    mut control_events: EventReader<ControlEvent>,
+    mut control: ResMut<CursorControl>,
+    mut register: ResMut<Register>,
+    bubbles: Res<Bubbles>,
+    q: StyleQuery,
+    mut bubble_commands: EventWriter<BubbleCommand>,
+) {
+    let lookup = |id| {
+        let (content, color, tfm) = q.get(bubbles.entity(id)?).ok()?;
+        Some((content.0.clone(), color.0, tfm.translation.truncate()))
+    };
+
+    for ev in control_events.iter() {
+        let result = match ev {
+            ControlEvent::Change(change) => apply_change(
+                *change,
+                &mut control,
+                &register,
+                &bubbles,
+                lookup,
+                &mut bubble_commands,
+            )
+            .map(|()| register.last_change = Some(*change)),
+            ControlEvent::Yank { depth } => control
+                .focused
+                .ok_or(ChangeError::NoFocus)
+                .and_then(|focused| Ok(bubbles.copy_subtree(focused, *depth, lookup)?))
+                .map(|copy| register.yanked = Some(copy)),
+            _ => continue,
+        };
+        if let Err(e) = result {
+            warn!("Cannot apply {ev:?}: {e}");
+        }
+    }
+}
+
+fn apply_change(
+    change: Change,
+    control: &mut CursorControl,
+    register: &Register,
+    bubbles: &Bubbles,
+    lookup: impl Fn(BubbleId) -> Option<(String, Color, Vec2)>,
+    bubble_commands: &mut EventWriter<BubbleCommand>,
+) -> Result<(), ChangeError> {
+    let focused = control.focused.ok_or(ChangeError::NoFocus)?;
+    let repeat = |command: BubbleCommand, count| std::iter::repeat_n(command, count);
+
+    match change {
+        Change::DeleteBubble | Change::DeleteSubtree { .. } => {
+            let depth = match change {
+                Change::DeleteSubtree { depth } => depth,
+                _ => Some(1),
+            };
+            let removed = bubbles.subtree(focused, depth)?;
+            let parent = bubbles
+                .neighbors(focused)?
+                .into_iter()
+                .find(|id| !removed.contains(id));
+            bubble_commands.send_batch(removed.iter().copied().map(BubbleCommand::Remove));
+            // Move on to the parent, like the cursor moves to the next line in vim. Other
+            // selected bubbles stay selected, the removed ones are forgotten once removed.
+            let selection_removed = control.selected.iter().all(|id| removed.contains(id));
+            if let (true, Some(parent)) = (selection_removed, parent) {
+                control.select(parent);
+            }
+        }
+        Change::Paste { count } => {
+            let copy = register.yanked.as_ref().ok_or(ChangeError::NothingYanked)?;
+            bubble_commands.send_batch(repeat(BubbleCommand::Paste(focused, copy.clone()), count));
+        }
+        Change::NewSibling { count } => match bubbles.neighbors(focused)?.first() {
+            Some(parent) => {
+                bubble_commands.send_batch(repeat(BubbleCommand::SpawnChild(*parent), count))
+            }
+            None => {
+                // Without a parent, line the new bubbles up next to the focused one
+                let (_, _, pos) = lookup(focused).ok_or(BubbleGraphError::NoPosition(focused))?;
+                let step = Vec2::X * BUBBLE_SIZE.x * 2.5;
+                bubble_commands.send_batch(
+                    (1..=count).map(|i| BubbleCommand::SpawnOrphan(pos + step * i as f32)),
+                );
+            }
+        },
+        Change::NewChild { count } => {
+            bubble_commands.send_batch(repeat(BubbleCommand::SpawnChild(focused), count))
+        }
+    }
+    Ok(())
+}
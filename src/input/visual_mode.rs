@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+
+use super::Action;
+use crate::{
+    camera::ControlEvent,
+    cursor_control::{BulkOp, InputMode},
+};
+
+/// Colors picked with [`Action::Recolor`], in order
+pub const PALETTE: [Color; 6] = [
+    Color::PURPLE,
+    Color::MIDNIGHT_BLUE,
+    Color::SEA_GREEN,
+    Color::OLIVE,
+    Color::MAROON,
+    Color::DARK_GRAY,
+];
+
+/// Extends the selection in a direction and applies bulk operations to it
+///
+/// Deleting and grouping return to travel mode, as does [`Action::Travel`], which keeps the
+/// selection.
+pub fn handle_action(action: Action, control_events: &mut EventWriter<ControlEvent>) {
+    let op = match action {
+        Action::Travel => {
+            control_events.send(ControlEvent::ChangeMode(InputMode::Travel));
+            return;
+        }
+        Action::Navigate(dir) => {
+            control_events.send(ControlEvent::Navigate { dir, times: 1 });
+            return;
+        }
+        Action::Delete => BulkOp::Delete,
+        Action::ConnectToFocused => BulkOp::ConnectToFocused,
+        Action::Group => BulkOp::Group,
+        Action::Recolor(idx) => match PALETTE.get(idx) {
+            Some(color) => BulkOp::SetColor(*color),
+            None => return,
+        },
+        _ => return,
+    };
+
+    let done = matches!(op, BulkOp::Delete | BulkOp::Group);
+    control_events.send(ControlEvent::Bulk(op));
+    // The selection is gone or merged, so there is nothing left to extend
+    if done {
+        control_events.send(ControlEvent::ChangeMode(InputMode::Travel));
+    }
+}
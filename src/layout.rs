@@ -0,0 +1,279 @@
+//! Headless layout computation
+//!
+//! Runs the same forces as [`physics_system`](crate::physics::physics_system) and
+//! `link_physics` on plain data, without a window or an ECS world. Useful for tests, exports and
+//! pre-layouting imported maps.
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    bubbles::{connected_components, BubbleId, SubgraphId},
+    physics::{
+        centering_force, is_resting, repel_force, slow_down, spring_force, BubblePhysics,
+        GlobalPhysics, SubgraphForces,
+    },
+};
+
+/// When to stop iterating the simulation
+#[derive(Clone, Debug)]
+pub struct Convergence {
+    /// Simulated time step per iteration, in seconds
+    pub dt: f32,
+    /// The layout is settled when no bubble moves faster than this, in pixels per second
+    pub max_speed: f32,
+    /// Give up after this many iterations even if the layout has not settled
+    pub max_iterations: usize,
+}
+
+impl Default for Convergence {
+    fn default() -> Self {
+        Self {
+            dt: 1. / 60.,
+            max_speed: 0.5,
+            max_iterations: 10_000,
+        }
+    }
+}
+
+/// Result of [`compute_layout`]
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub positions: HashMap<BubbleId, Vec2>,
+    /// Number of iterations that were run
+    pub iterations: usize,
+    /// False if the iteration cap was reached before the layout settled
+    pub converged: bool,
+}
+
+/// Simulates the graph until it settles and returns the final position of each bubble
+///
+/// # Arguments
+///
+/// * `initial` - Starting position and physical properties of each bubble. Bubbles on top of each
+///   other will not be separated, so the positions should be distinct.
+/// * `edges` - Connections as `(from, to)`, e.g., from [`Bubbles::edges`](crate::bubbles::Bubbles::edges)
+pub fn compute_layout(
+    initial: &HashMap<BubbleId, (Vec2, BubblePhysics)>,
+    edges: &[(BubbleId, BubbleId)],
+    gphysics: &GlobalPhysics,
+    criteria: &Convergence,
+) -> Layout {
+    let mut run = LayoutRun::new(initial, edges, criteria.clone());
+    run.advance(gphysics, criteria.max_iterations);
+    run.layout()
+}
+
+/// A [`compute_layout`] that runs a few iterations at a time, e.g., some on each frame
+pub struct LayoutRun {
+    sim: Simulation,
+    criteria: Convergence,
+    iterations: usize,
+    converged: bool,
+}
+
+impl LayoutRun {
+    /// See [`compute_layout`] for the arguments
+    pub fn new(
+        initial: &HashMap<BubbleId, (Vec2, BubblePhysics)>,
+        edges: &[(BubbleId, BubbleId)],
+        criteria: Convergence,
+    ) -> Self {
+        Self {
+            sim: Simulation::new(initial, edges),
+            criteria,
+            iterations: 0,
+            converged: false,
+        }
+    }
+
+    /// Runs up to `budget` more iterations and returns true once the run is done, i.e., the
+    /// layout has settled or the iteration cap was reached
+    pub fn advance(&mut self, gphysics: &GlobalPhysics, budget: usize) -> bool {
+        for _ in 0..budget {
+            if self.is_done() {
+                break;
+            }
+            let max_speed = self.sim.step(gphysics, self.criteria.dt);
+            self.iterations += 1;
+            self.converged = max_speed <= self.criteria.max_speed;
+        }
+        self.is_done()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.converged || self.iterations >= self.criteria.max_iterations
+    }
+
+    /// Positions reached so far
+    pub fn layout(&self) -> Layout {
+        Layout {
+            positions: self.sim.positions(),
+            iterations: self.iterations,
+            converged: self.converged,
+        }
+    }
+}
+
+/// Bubbles stored as flat arrays, indexed in order of `BubbleId`
+struct Simulation {
+    ids: Vec<BubbleId>,
+    pos: Vec<Vec2>,
+    bodies: Vec<BubblePhysics>,
+    /// Connections as `(from, to)` indices
+    links: Vec<(usize, usize)>,
+    subgraphs: Vec<SubgraphId>,
+}
+
+impl Simulation {
+    fn new(
+        initial: &HashMap<BubbleId, (Vec2, BubblePhysics)>,
+        edges: &[(BubbleId, BubbleId)],
+    ) -> Self {
+        // Sort to make the summation order, and thus the result, reproducible
+        let mut ids = initial.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let (pos, bodies) = ids.iter().map(|id| initial[id].clone()).unzip();
+
+        let idx_by_id = ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| (*id, idx))
+            .collect::<HashMap<_, _>>();
+        // Edges to bubbles without a position are ignored, like in `link_physics`
+        let links = edges
+            .iter()
+            .filter_map(|(from, to)| Some((*idx_by_id.get(from)?, *idx_by_id.get(to)?)))
+            .collect();
+
+        let subgraph_by_id = connected_components(&ids, edges);
+        let subgraphs = ids.iter().map(|id| subgraph_by_id[id]).collect();
+
+        Self {
+            ids,
+            pos,
+            bodies,
+            links,
+            subgraphs,
+        }
+    }
+
+    /// Advances the simulation by `dt` in the same order as the ECS systems: links, global forces,
+    /// then repels
+    ///
+    /// Returns the speed of the fastest bubble that moved. Resting bubbles do not move, even if
+    /// forces keep their velocity just above what the convergence criteria allow.
+    fn step(&mut self, gphysics: &GlobalPhysics, dt: f32) -> f32 {
+        let mut max_speed: f32 = 0.;
+        for (from, to) in &self.links {
+            let x = self.pos[*to] - self.pos[*from];
+            let body = &mut self.bodies[*from];
+            body.vel +=
+                body.acceleration(spring_force(gphysics.flink, gphysics.link_length, x)) * dt;
+        }
+
+        let subgraphs = SubgraphForces::new(
+            self.subgraphs.iter().copied().zip(self.pos.iter().copied()),
+            gphysics.fsubgraph_repel,
+            gphysics.forigin(),
+        );
+        for ((pos, body), subgraph) in self
+            .pos
+            .iter_mut()
+            .zip(self.bodies.iter_mut())
+            .zip(self.subgraphs.iter())
+        {
+            let center = subgraphs.centroid(*subgraph);
+            body.vel += body.acceleration(centering_force(*pos, center, gphysics.fcenter)) * dt;
+            body.vel += subgraphs.push(*subgraph) * dt;
+            body.vel = slow_down(body.vel, gphysics.slow_mult, dt);
+            if body.pinned {
+                body.vel = Vec2::ZERO;
+            } else if !is_resting(body.vel) {
+                *pos += body.vel * dt;
+                max_speed = max_speed.max(body.vel.length());
+            }
+        }
+
+        let charges = self
+            .pos
+            .iter()
+            .zip(self.bodies.iter())
+            .map(|(pos, body)| (*pos, body.charge))
+            .collect::<Vec<_>>();
+        for (idx, body) in self.bodies.iter_mut().enumerate() {
+            let f = repel_force(idx, charges[idx].0, body.charge, &charges, gphysics.frepel);
+            body.vel += body.acceleration(f) * dt;
+        }
+        max_speed
+    }
+
+    fn positions(&self) -> HashMap<BubbleId, Vec2> {
+        self.ids
+            .iter()
+            .copied()
+            .zip(self.pos.iter().copied())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orphans(positions: &[Vec2]) -> HashMap<BubbleId, (Vec2, BubblePhysics)> {
+        positions
+            .iter()
+            .enumerate()
+            .map(|(id, pos)| (id as BubbleId, (*pos, BubblePhysics::default())))
+            .collect()
+    }
+
+    #[test]
+    fn converges() {
+        let initial = orphans(&[Vec2::new(-100., 0.), Vec2::new(100., 0.)]);
+        let criteria = Convergence::default();
+        let layout = compute_layout(&initial, &[], &GlobalPhysics::default(), &criteria);
+
+        assert!(layout.converged);
+        assert!(layout.iterations < criteria.max_iterations);
+        // The two bubbles push each other apart, symmetrically around the origin
+        let (left, right) = (layout.positions[&0], layout.positions[&1]);
+        assert!(right.x - left.x > 200.);
+        assert!((left + right).length() < 1.);
+    }
+
+    #[test]
+    fn stops_at_iteration_cap() {
+        let initial = orphans(&[Vec2::new(-10., 0.), Vec2::new(10., 0.)]);
+        let criteria = Convergence {
+            max_iterations: 5,
+            ..default()
+        };
+        let layout = compute_layout(&initial, &[], &GlobalPhysics::default(), &criteria);
+
+        assert!(!layout.converged);
+        assert_eq!(layout.iterations, 5);
+        assert_eq!(layout.positions.len(), 2);
+    }
+
+    #[test]
+    fn run_in_batches_matches_compute_layout() {
+        let initial = orphans(&[
+            Vec2::new(-100., 0.),
+            Vec2::new(100., 0.),
+            Vec2::new(0., 50.),
+        ]);
+        let gphysics = GlobalPhysics::default();
+        let whole = compute_layout(&initial, &[(2, 0)], &gphysics, &Convergence::default());
+
+        let mut run = LayoutRun::new(&initial, &[(2, 0)], Convergence::default());
+        let mut batches = 1;
+        while !run.advance(&gphysics, 10) {
+            batches += 1;
+        }
+        let batched = run.layout();
+
+        assert_eq!(batches, whole.iterations.div_ceil(10));
+        assert_eq!(batched.iterations, whole.iterations);
+        assert_eq!(batched.positions, whole.positions);
+    }
+}
@@ -1,7 +1,78 @@
-use zeal::default_app;
+use bevy::log::LogPlugin;
+use zeal::{
+    default_app, headless_app,
+    rng::SeededRng,
+    session::{SessionPlugin, SessionRecorder, SessionReplay},
+};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse(std::env::args().skip(1))?;
+
+    if let Some(path) = &args.replay {
+        let mut replay = SessionReplay::load(path)?;
+        replay.exit_when_done = args.headless;
+        let mut app = if args.headless {
+            let mut app = headless_app();
+            // Show the summary and any warnings from the replay
+            app.add_plugins(LogPlugin::default());
+            app
+        } else {
+            default_app()
+        };
+        app.add_plugins(SessionPlugin);
+        replay.insert_into(&mut app);
+        app.run();
+        return Ok(());
+    }
+
     let mut app = default_app();
+    if let Some(seed) = args.seed {
+        app.insert_resource(SeededRng::from_seed(seed));
+    }
+    if let Some(path) = &args.record {
+        app.add_plugins(SessionPlugin)
+            .insert_resource(SessionRecorder::create(path)?);
+    }
     app.run();
     Ok(())
 }
+
+#[derive(Default)]
+struct Args {
+    /// `--seed <u64>`: seed for all random decisions, picked at random if not given
+    seed: Option<u64>,
+    /// `--record <file>`: write a session log for replaying the session later
+    record: Option<String>,
+    /// `--replay <file>`: replay a session log, with the seed from the log
+    replay: Option<String>,
+    /// `--headless`: replay without a window and exit when done
+    headless: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut ret = Args::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("--seed requires a value")?;
+                    let seed = value
+                        .parse()
+                        .map_err(|e| format!("invalid seed '{value}': {e}"))?;
+                    ret.seed = Some(seed);
+                }
+                "--record" => ret.record = Some(args.next().ok_or("--record requires a file")?),
+                "--replay" => ret.replay = Some(args.next().ok_or("--replay requires a file")?),
+                "--headless" => ret.headless = true,
+                other => return Err(format!("unknown argument '{other}'")),
+            }
+        }
+        if ret.replay.is_some() && (ret.record.is_some() || ret.seed.is_some()) {
+            return Err("--replay cannot be combined with --record or --seed".into());
+        }
+        if ret.headless && ret.replay.is_none() {
+            return Err("--headless requires --replay".into());
+        }
+        Ok(ret)
+    }
+}
@@ -1,18 +1,74 @@
-use std::mem;
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
 
-use bevy::prelude::*;
+use crate::{
+    bubbles::{apply_bubble_commands, BubbleContent, Bubbles, SubgraphId},
+    debug_overlay::{record_physics_stats, PhysicsStats},
+    presets::{reload_presets, PhysicsPresets},
+    session::LiveInput,
+    GraphBubble,
+};
 
 pub const DEFAULT_FCENTER: f32 = 3.;
 pub const DEFAULT_SLOW_MULT: f32 = 10.;
 pub const DEFAULT_FREPEL: f32 = 10_000_000.;
 pub const DEFAULT_FLINK: f32 = 4.;
+pub const DEFAULT_LINK_LENGTH: f32 = 0.;
+pub const DEFAULT_FSUBGRAPH_REPEL: f32 = 10_000_000.;
+/// Pull of each subgraph centroid towards the origin, as a fraction of `fcenter`
+///
+/// Weak enough not to disturb the layout of a subgraph, but anchors lone bubbles and keeps
+/// disconnected subgraphs from drifting apart forever.
+pub const ORIGIN_PULL_MULT: f32 = 0.03;
+
+type BubbleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut BubblePhysics,
+        &'static mut Transform,
+        &'static GraphBubble,
+    ),
+>;
+
+/// Moves bubbles according to [`GlobalPhysics`]
+///
+/// Requires [`GraphPlugin`](crate::bubbles::GraphPlugin).
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GlobalPhysics>()
+            .init_resource::<PhysicsPresets>()
+            .init_resource::<PhysicsStats>()
+            .add_systems(
+                Update,
+                (
+                    reload_presets.in_set(LiveInput),
+                    weigh_bubbles,
+                    physics_system,
+                )
+                    .chain()
+                    .after(apply_bubble_commands),
+            )
+            .add_systems(Update, link_physics)
+            .add_systems(
+                Update,
+                record_physics_stats
+                    .after(physics_system)
+                    .after(link_physics),
+            );
+    }
+}
 
 #[derive(Resource)]
 pub struct GlobalPhysics {
-    // TODO: merge all forces into `active_forces`
     /// Centering force
     ///
-    /// Each bubble is accelerated towards [0, 0] at `fcenter` per second
+    /// Each bubble is accelerated towards the centroid of its own subgraph, i.e., connected
+    /// component, at `fcenter` per second, and each subgraph towards the origin, see
+    /// [`ORIGIN_PULL_MULT`]
     pub fcenter: f32,
     /// The speed of each bubble is multiplied by `speed_mult` per second
     pub slow_mult: f32,
@@ -20,154 +76,577 @@ pub struct GlobalPhysics {
     ///
     /// TODO: should apply based on distance between bubble edges, not centers
     pub frepel: f32,
-    /// Link pull multiplier per distance (k in kx), i.e., stiffness
+    /// Link pull multiplier per distance (k in k(x - L)), i.e., stiffness
     pub flink: f32,
-    active_forces: Vec<Force>,
+    /// Rest length of links (L in k(x - L))
+    pub link_length: f32,
+    /// Repel force multiplier between subgraphs
+    ///
+    /// Applies between subgraph centroids and moves all bubbles of a subgraph alike
+    pub fsubgraph_repel: f32,
+    /// Update bubbles in parallel on the compute task pool
+    ///
+    /// Produces the same results as the serial update, which is used if this is false.
+    pub parallel: bool,
 }
 
 impl Default for GlobalPhysics {
     fn default() -> Self {
         // Configure physics
-
-        let fcenter = DEFAULT_FCENTER;
-        let slow_mult = DEFAULT_SLOW_MULT;
-        let frepel = DEFAULT_FREPEL;
-        let flink = DEFAULT_FLINK;
+        let PhysicsParams {
+            fcenter,
+            slow_mult,
+            frepel,
+            flink,
+            link_length,
+            fsubgraph_repel,
+        } = PhysicsParams::default();
 
         Self {
             fcenter,
             slow_mult,
             frepel,
             flink,
-            active_forces: vec![],
+            link_length,
+            fsubgraph_repel,
+            parallel: true,
+        }
+    }
+}
+
+/// The tunable parameters of [`GlobalPhysics`], e.g., for saving presets
+///
+/// See [`GlobalPhysics`] for a description of each field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsParams {
+    pub fcenter: f32,
+    pub slow_mult: f32,
+    pub frepel: f32,
+    pub flink: f32,
+    pub link_length: f32,
+    pub fsubgraph_repel: f32,
+}
+
+impl Default for PhysicsParams {
+    fn default() -> Self {
+        Self {
+            fcenter: DEFAULT_FCENTER,
+            slow_mult: DEFAULT_SLOW_MULT,
+            frepel: DEFAULT_FREPEL,
+            flink: DEFAULT_FLINK,
+            link_length: DEFAULT_LINK_LENGTH,
+            fsubgraph_repel: DEFAULT_FSUBGRAPH_REPEL,
         }
     }
 }
 
 impl GlobalPhysics {
-    pub fn add_force(&mut self, f: Force) {
-        self.active_forces.push(f);
+    pub fn params(&self) -> PhysicsParams {
+        PhysicsParams {
+            fcenter: self.fcenter,
+            slow_mult: self.slow_mult,
+            frepel: self.frepel,
+            flink: self.flink,
+            link_length: self.link_length,
+            fsubgraph_repel: self.fsubgraph_repel,
+        }
+    }
+
+    /// Replaces all tunable parameters, leaving active forces untouched
+    pub fn set_params(&mut self, params: &PhysicsParams) {
+        self.fcenter = params.fcenter;
+        self.slow_mult = params.slow_mult;
+        self.frepel = params.frepel;
+        self.flink = params.flink;
+        self.link_length = params.link_length;
+        self.fsubgraph_repel = params.fsubgraph_repel;
+    }
+
+    /// Pull of subgraph centroids towards the origin, see [`ORIGIN_PULL_MULT`]
+    pub fn forigin(&self) -> f32 {
+        self.fcenter * ORIGIN_PULL_MULT
     }
 
-    pub fn update_bubbles(&mut self, dt: f32, mut q: Query<(&mut BubblePhysics, &mut Transform)>) {
-        self.update_global_forces(dt, &mut q);
+    pub fn update_bubbles(&mut self, dt: f32, bubbles: &Bubbles, mut q: BubbleQuery) {
+        self.update_global_forces(dt, bubbles, &mut q);
         self.update_repels(dt, &mut q);
     }
 
-    fn update_global_forces(
-        &mut self,
+    fn update_global_forces(&mut self, dt: f32, bubbles: &Bubbles, q: &mut BubbleQuery) {
+        let subgraphs = SubgraphForces::new(
+            q.iter().filter_map(|(_, _, tfm, id)| {
+                Some((bubbles.subgraph(id.0)?, tfm.translation.truncate()))
+            }),
+            self.fsubgraph_repel,
+            self.forigin(),
+        );
+
+        if self.parallel {
+            q.par_iter_mut()
+                .for_each_mut(|(_, mut bubble, mut tfm, id)| {
+                    let subgraph = bubbles.subgraph(id.0);
+                    self.apply_global_forces(dt, subgraph, &subgraphs, &mut bubble, &mut tfm);
+                });
+        } else {
+            for (_, mut bubble, mut tfm, id) in q.iter_mut() {
+                let subgraph = bubbles.subgraph(id.0);
+                self.apply_global_forces(dt, subgraph, &subgraphs, &mut bubble, &mut tfm);
+            }
+        }
+    }
+
+    /// Applies the global forces and slow down to a single bubble, and moves it
+    fn apply_global_forces(
+        &self,
         dt: f32,
-        q: &mut Query<(&mut BubblePhysics, &mut Transform)>,
+        subgraph: Option<SubgraphId>,
+        subgraphs: &SubgraphForces,
+        bubble: &mut BubblePhysics,
+        tfm: &mut Mut<Transform>,
     ) {
-        for (mut bubble, mut tfm) in q.iter_mut() {
-            let pos = Vec2::new(tfm.translation.x, tfm.translation.y);
+        let pos = Vec2::new(tfm.translation.x, tfm.translation.y);
 
+        if let Some(subgraph) = subgraph {
             // Apply centering force
-            let fcenter = -pos * self.fcenter;
-            bubble.vel += fcenter * dt;
+            let center = subgraphs.centroid(subgraph);
+            let a = bubble.acceleration(centering_force(pos, center, self.fcenter));
+            bubble.vel += a * dt;
+            bubble.forces.center = a;
 
-            // Apply other forces
-            for f in &self.active_forces {
-                let v = f.effect_on_point(pos);
-                bubble.vel += v;
-            }
+            // Push away from other subgraphs and pull towards the origin
+            let a = subgraphs.push(subgraph);
+            bubble.vel += a * dt;
+            bubble.forces.subgraph = a;
+        } else {
+            bubble.forces.center = Vec2::ZERO;
+            bubble.forces.subgraph = Vec2::ZERO;
+        }
 
-            // Apply slow down
-            // TODO: find a good formula
-            bubble.vel *= 1. - self.slow_mult * dt;
+        // Apply slow down
+        bubble.vel = slow_down(bubble.vel, self.slow_mult, dt);
 
-            // Apply velocity
-            let delta = bubble.vel * dt;
-            tfm.translation = Vec3::new(
-                tfm.translation.x + delta.x,
-                tfm.translation.y + delta.y,
-                tfm.translation.z,
-            );
+        // Pinned bubbles feel the forces, for debugging, but stay where they are
+        if bubble.pinned {
+            bubble.vel = Vec2::ZERO;
+            return;
         }
 
-        // Retain non-explosion forces
-        self.active_forces.retain(|f| {
-            mem::discriminant(f)
-                != mem::discriminant(&Force::Explosion {
-                    origin: Vec2::ZERO,
-                    force: 0.,
-                    r: 0.,
-                })
-        })
+        // Apply velocity, unless the bubble is resting. Leaving the transform untouched lets
+        // change detection skip work for resting bubbles.
+        if is_resting(bubble.vel) {
+            return;
+        }
+        let delta = bubble.vel * dt;
+        tfm.translation = Vec3::new(
+            tfm.translation.x + delta.x,
+            tfm.translation.y + delta.y,
+            tfm.translation.z,
+        );
     }
 
-    fn update_repels(&self, dt: f32, q: &mut Query<(&mut BubblePhysics, &mut Transform)>) {
-        let positions = q
+    fn update_repels(&self, dt: f32, q: &mut BubbleQuery) {
+        let charges = q
             .iter()
-            .map(|(_, tfm)| Vec2::new(tfm.translation.x, tfm.translation.y))
+            .map(|(_, bubble, tfm, _)| {
+                (
+                    Vec2::new(tfm.translation.x, tfm.translation.y),
+                    bubble.charge,
+                )
+            })
             .collect::<Vec<_>>();
-        for (bidx, (mut bubble, tfm)) in q.iter_mut().enumerate() {
+        // Identify each bubble by its index in `charges`, in both serial and parallel updates
+        let idx_by_entity = q
+            .iter()
+            .enumerate()
+            .map(|(idx, (entity, ..))| (entity, idx))
+            .collect::<HashMap<_, _>>();
+
+        let apply_repel = |bidx: usize, bubble: &mut BubblePhysics, tfm: &Transform| {
             let pos = Vec2::new(tfm.translation.x, tfm.translation.y);
 
             // Apply repel
             // TODO: optimize by separating to another system and filtering based on distance
-            let one_div_by_distances_squared: Vec2 = positions
-                .iter()
-                .enumerate()
-                // Do not repel self
-                .filter(|(oidx, _)| bidx != *oidx)
-                .map(|(_, opos)| {
-                    let diff = pos - *opos;
-                    let rdist = 1. / (diff.length() * diff.length());
-                    let unit = diff.normalize();
-                    unit * rdist
-                })
-                .sum();
-            bubble.vel += one_div_by_distances_squared * self.frepel * dt;
+            let f = repel_force(bidx, pos, bubble.charge, &charges, self.frepel);
+            let a = bubble.acceleration(f);
+            bubble.vel += a * dt;
+            bubble.forces.repel = a;
+        };
+
+        if self.parallel {
+            q.par_iter_mut()
+                .for_each_mut(|(entity, mut bubble, tfm, _)| {
+                    apply_repel(idx_by_entity[&entity], &mut bubble, &tfm)
+                });
+        } else {
+            for (entity, mut bubble, tfm, _) in q.iter_mut() {
+                apply_repel(idx_by_entity[&entity], &mut bubble, &tfm);
+            }
         }
     }
 }
 
+/// Pull towards `center`, `fcenter` per unit of distance
+pub(crate) fn centering_force(pos: Vec2, center: Vec2, fcenter: f32) -> Vec2 {
+    (center - pos) * fcenter
+}
+
+/// Centroid of each subgraph and the acceleration pushing it away from the other subgraphs and
+/// pulling it towards the origin
+pub(crate) struct SubgraphForces {
+    centroids: HashMap<SubgraphId, Vec2>,
+    pushes: HashMap<SubgraphId, Vec2>,
+}
+
+impl SubgraphForces {
+    /// # Arguments
+    ///
+    /// * `members` - Subgraph and position of each bubble
+    /// * `frepel` - Repel force multiplier between subgraph centroids
+    /// * `forigin` - Pull of each centroid towards the origin per unit of distance
+    pub(crate) fn new(
+        members: impl Iterator<Item = (SubgraphId, Vec2)>,
+        frepel: f32,
+        forigin: f32,
+    ) -> Self {
+        let mut sums: HashMap<SubgraphId, (Vec2, f32)> = HashMap::new();
+        for (subgraph, pos) in members {
+            let (sum, count) = sums.entry(subgraph).or_insert((Vec2::ZERO, 0.));
+            *sum += pos;
+            *count += 1.;
+        }
+
+        // Sort to make the summation order, and thus the result, reproducible
+        let mut centroids = sums
+            .into_iter()
+            .map(|(subgraph, (sum, count))| (subgraph, sum / count))
+            .collect::<Vec<_>>();
+        centroids.sort_unstable_by_key(|(subgraph, _)| *subgraph);
+
+        let charges = centroids
+            .iter()
+            .map(|(_, centroid)| (*centroid, 1.))
+            .collect::<Vec<_>>();
+        let pushes = centroids
+            .iter()
+            .enumerate()
+            .map(|(idx, (subgraph, centroid))| {
+                let push = repel_force(idx, *centroid, 1., &charges, frepel);
+                let pull = centering_force(*centroid, Vec2::ZERO, forigin);
+                (*subgraph, push + pull)
+            })
+            .collect();
+
+        Self {
+            centroids: centroids.into_iter().collect(),
+            pushes,
+        }
+    }
+
+    pub(crate) fn centroid(&self, subgraph: SubgraphId) -> Vec2 {
+        self.centroids[&subgraph]
+    }
+
+    pub(crate) fn push(&self, subgraph: SubgraphId) -> Vec2 {
+        self.pushes[&subgraph]
+    }
+}
+
+/// Sum of inverse-square repels from all `charges` except the one at `self_idx`
+///
+/// Each repel is scaled by the product of the two charges, like in Coulomb's law.
+///
+/// # Arguments
+///
+/// * `charges` - Position and charge of each bubble
+pub(crate) fn repel_force(
+    self_idx: usize,
+    pos: Vec2,
+    charge: f32,
+    charges: &[(Vec2, f32)],
+    frepel: f32,
+) -> Vec2 {
+    let charge_div_by_distances_squared: Vec2 = charges
+        .iter()
+        .enumerate()
+        // Do not repel self
+        .filter(|(oidx, _)| self_idx != *oidx)
+        .map(|(_, (opos, ocharge))| {
+            let diff = pos - *opos;
+            let rdist = 1. / (diff.length() * diff.length());
+            let unit = diff.normalize();
+            unit * rdist * *ocharge
+        })
+        .sum();
+    charge_div_by_distances_squared * charge * frepel
+}
+
+/// According to Hooke's law
+///
+/// k * (|x| - L), in the direction of x, where
+///
+/// - k is stiffness
+/// - x is distance
+/// - L is rest length
+pub(crate) fn spring_force(stiffness: f32, rest_length: f32, dist: Vec2) -> Vec2 {
+    let len = dist.length();
+    if len == 0. {
+        return Vec2::ZERO;
+    }
+    stiffness * (len - rest_length) * dist / len
+}
+
+/// Bubbles slower than this are not moved, in pixels per second
+pub const REST_SPEED: f32 = 1.;
+
+/// True if the bubble is too slow to be moved, see [`REST_SPEED`]
+pub(crate) fn is_resting(vel: Vec2) -> bool {
+    vel.length_squared() < REST_SPEED * REST_SPEED
+}
+
+/// Velocity after applying the slow down for `dt` seconds
+// TODO: find a good formula
+pub(crate) fn slow_down(vel: Vec2, slow_mult: f32, dt: f32) -> Vec2 {
+    vel * (1. - slow_mult * dt)
+}
+
 /// Physical state
-#[derive(Component, Default)]
+#[derive(Component, Clone, Debug)]
 pub struct BubblePhysics {
     /// Velocity
     pub vel: Vec2,
+    /// Forces are divided by mass, so heavy bubbles move less
+    pub mass: f32,
+    /// Repel force multiplier, so bubbles with a high charge push harder
+    pub charge: f32,
+    /// Accelerations applied on the last update, for debugging
+    pub forces: ForceBreakdown,
+    /// Pinned bubbles are not moved by forces
+    pub pinned: bool,
+}
+
+/// Accelerations applied to a bubble on the last update, by source
+#[derive(Clone, Debug, Default)]
+pub struct ForceBreakdown {
+    /// Towards the centroid of the bubble's subgraph
+    pub center: Vec2,
+    /// Away from other subgraphs and towards the origin
+    pub subgraph: Vec2,
+    /// Away from other bubbles
+    pub repel: Vec2,
+    /// Along links to connected bubbles
+    pub link: Vec2,
+}
+
+impl ForceBreakdown {
+    pub fn total(&self) -> Vec2 {
+        self.center + self.subgraph + self.repel + self.link
+    }
+}
+
+impl Default for BubblePhysics {
+    fn default() -> Self {
+        Self {
+            vel: Vec2::ZERO,
+            mass: 1.,
+            charge: 1.,
+            forces: ForceBreakdown::default(),
+            pinned: false,
+        }
+    }
+}
+
+impl BubblePhysics {
+    /// Added mass per character of content
+    const MASS_PER_CHAR: f32 = 0.02;
+    /// Added mass per connection, in either direction
+    const MASS_PER_LINK: f32 = 0.5;
+
+    /// Derives mass and charge from the bubble's content length and number of connections
+    ///
+    /// A bubble with no content and no connections has a mass and a charge of 1. Charge grows with
+    /// the square root of mass, so hubs and long texts push harder but not as much as they weigh.
+    pub fn from_content(content_len: usize, degree: usize) -> Self {
+        let mut physics = Self::default();
+        physics.weigh(content_len, degree);
+        physics
+    }
+
+    /// Updates mass and charge like [`BubblePhysics::from_content`], keeping the velocity
+    pub fn weigh(&mut self, content_len: usize, degree: usize) {
+        self.mass =
+            1. + content_len as f32 * Self::MASS_PER_CHAR + degree as f32 * Self::MASS_PER_LINK;
+        self.charge = self.mass.sqrt();
+    }
+
+    /// Acceleration caused by `force`, i.e., F / m
+    pub fn acceleration(&self, force: Vec2) -> Vec2 {
+        force / self.mass
+    }
+
+    pub fn kinetic_energy(&self) -> f32 {
+        0.5 * self.mass * self.vel.length_squared()
+    }
+}
+
+/// Updates the mass and charge of bubbles whose content or connections changed
+pub fn weigh_bubbles(
+    mut q: Query<(&mut BubblePhysics, Ref<BubbleContent>, &GraphBubble)>,
+    bubbles: Res<Bubbles>,
+) {
+    let graph_changed = bubbles.is_changed();
+    for (mut physics, content, id) in q.iter_mut() {
+        if graph_changed || content.is_changed() || physics.is_added() {
+            physics.weigh(content.0.len(), bubbles.degree(id.0));
+        }
+    }
+}
+
+/// Pulls each bubble towards the bubbles it links to
+pub fn link_physics(
+    mut q: Query<(&mut BubblePhysics, &Transform, &GraphBubble)>,
+    transforms: Query<&Transform, With<GraphBubble>>,
+    time: Res<Time>,
+    bubbles: Res<Bubbles>,
+    gphysics: Res<GlobalPhysics>,
+) {
+    let position = |id| {
+        let tfm = transforms.get(bubbles.entity(id)?).ok()?;
+        Some(tfm.translation.truncate())
+    };
+
+    let apply_links = |phys: &mut BubblePhysics, tfm: &Transform, id: &GraphBubble| {
+        let origin = tfm.translation.truncate();
+        // Bubbles removed this frame are still in the world until commands are applied
+        let links = bubbles.neighbors(id.0).unwrap_or_default();
+        let mut link_total = Vec2::ZERO;
+        for id in links {
+            // Bubbles spawned this frame have no transform until commands are applied
+            if let Some(tgt) = position(id) {
+                // dv = k*x + b
+                let x = tgt - origin;
+                let k = gphysics.flink;
+                let a = phys.acceleration(spring_force(k, gphysics.link_length, x));
+                phys.vel += a * time.delta_seconds();
+                link_total += a;
+            }
+        }
+        phys.forces.link = link_total;
+    };
+
+    if gphysics.parallel {
+        q.par_iter_mut()
+            .for_each_mut(|(mut phys, tfm, id)| apply_links(&mut phys, tfm, id));
+    } else {
+        for (mut phys, tfm, id) in q.iter_mut() {
+            apply_links(&mut phys, tfm, id);
+        }
+    }
 }
 
 pub fn physics_system(
     time: Res<Time>,
     mut gphysics: ResMut<GlobalPhysics>,
-    q: Query<(&mut BubblePhysics, &mut Transform)>,
+    bubbles: Res<Bubbles>,
+    q: BubbleQuery,
 ) {
     let dt = time.delta_seconds();
-    gphysics.update_bubbles(dt, q);
+    gphysics.update_bubbles(dt, &bubbles, q);
 }
 
-#[derive(Clone)]
-pub enum Force {
-    /// Instant radial force, like an explosion
-    ///
-    /// Force decreases exponentially with radius
-    Explosion {
-        origin: Vec2,
-        /// Force at origin
-        force: f32,
-        /// Radius
-        r: f32,
-    },
-}
-
-impl Force {
-    fn effect_on_point(&self, pos: Vec2) -> Vec2 {
-        match self {
-            Force::Explosion { origin, force, r } => {
-                let diff = pos - *origin;
-                let distance_from_center = diff.length();
-                if distance_from_center <= *r {
-                    let frac = 1. - distance_from_center / r;
-                    let sq = frac * frac;
-                    debug_assert!(sq <= 1.0);
-                    force * sq * diff.normalize()
-                } else {
-                    Vec2::ZERO
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::core::TaskPoolPlugin;
+    use rand::Rng;
+
+    use super::*;
+    use crate::{
+        bubbles::{BubbleBundleBuilder, BubbleId, GraphPlugin},
+        rng::SeededRng,
+    };
+
+    /// Simulates the same random tree for a few fixed time steps and returns the final positions,
+    /// ordered by ID
+    fn simulate(parallel: bool) -> Vec<(BubbleId, Vec2)> {
+        let spawn_tree =
+            |mut bubbles: ResMut<Bubbles>, mut rng: ResMut<SeededRng>, mut commands: Commands| {
+                let mut builder = BubbleBundleBuilder::new(&mut commands);
+                let mut ids = vec![bubbles.spawn_orphan(Vec2::ZERO, &mut builder)];
+                while ids.len() < 200 {
+                    let parent = ids[rng.gen_range(0..ids.len())];
+                    let child = bubbles
+                        .spawn_child(parent, &mut builder, rng.as_mut())
+                        .unwrap();
+                    ids.push(child);
                 }
-            }
+            };
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), GraphPlugin, PhysicsPlugin))
+            .insert_resource(SeededRng::from_seed(1))
+            .init_resource::<Time>()
+            .add_systems(Startup, spawn_tree);
+        app.world.resource_mut::<GlobalPhysics>().parallel = parallel;
+
+        let mut now = Instant::now();
+        app.world.resource_mut::<Time>().update_with_instant(now);
+        app.update();
+        for _ in 0..20 {
+            now += Duration::from_millis(16);
+            app.world.resource_mut::<Time>().update_with_instant(now);
+            app.update();
+        }
+
+        let mut positions = app
+            .world
+            .query::<(&GraphBubble, &Transform)>()
+            .iter(&app.world)
+            .map(|(id, tfm)| (id.0, tfm.translation.truncate()))
+            .collect::<Vec<_>>();
+        positions.sort_by_key(|(id, _)| *id);
+        positions
+    }
+
+    #[test]
+    fn parallel_update_matches_serial() {
+        let serial = simulate(false);
+        assert_eq!(serial.len(), 200);
+        assert_eq!(serial, simulate(true));
+    }
+
+    #[test]
+    fn hubs_are_heavier() {
+        let spawn_star =
+            |mut bubbles: ResMut<Bubbles>, mut rng: ResMut<SeededRng>, mut commands: Commands| {
+                let mut builder = BubbleBundleBuilder::new(&mut commands);
+                let hub = bubbles.spawn_orphan(Vec2::ZERO, &mut builder);
+                for _ in 0..3 {
+                    bubbles
+                        .spawn_child(hub, &mut builder, rng.as_mut())
+                        .unwrap();
+                }
+            };
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), GraphPlugin, PhysicsPlugin))
+            .insert_resource(SeededRng::from_seed(1))
+            .init_resource::<Time>()
+            .add_systems(Startup, spawn_star);
+        app.update();
+
+        let masses = app
+            .world
+            .query::<(&GraphBubble, &BubblePhysics)>()
+            .iter(&app.world)
+            .map(|(id, physics)| (id.0, physics.mass))
+            .collect::<HashMap<_, _>>();
+        let hub = masses[&0];
+        assert_eq!(hub, BubblePhysics::from_content(1, 3).mass);
+        for leaf in 1..4 {
+            assert_eq!(masses[&leaf], BubblePhysics::from_content(1, 1).mass);
+            assert!(masses[&leaf] < hub);
         }
     }
 }
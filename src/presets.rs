@@ -0,0 +1,216 @@
+//! Named physics presets, stored in a RON config file
+//!
+//! The file is reloaded whenever it changes on disk.
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::physics::{GlobalPhysics, PhysicsParams};
+
+pub const DEFAULT_PRESETS_PATH: &str = "config/physics_presets.ron";
+/// How often the presets file is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug)]
+pub enum PresetError {
+    #[error("could not access {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("could not parse {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: ron::error::SpannedError,
+    },
+    #[error("could not serialize presets: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("no preset named '{0}'")]
+    NotFound(String),
+}
+
+/// Contents of the presets file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PresetFile {
+    pub presets: BTreeMap<String, PhysicsParams>,
+}
+
+impl Default for PresetFile {
+    /// The built-in presets, used when there is no presets file yet
+    fn default() -> Self {
+        let defaults = PhysicsParams::default();
+        let presets = [
+            (
+                "tight",
+                PhysicsParams {
+                    fcenter: 10.,
+                    frepel: 3_000_000.,
+                    flink: 10.,
+                    ..defaults.clone()
+                },
+            ),
+            (
+                "airy",
+                PhysicsParams {
+                    fcenter: 1.,
+                    slow_mult: 5.,
+                    frepel: 30_000_000.,
+                    flink: 2.,
+                    link_length: 150.,
+                    ..defaults.clone()
+                },
+            ),
+            (
+                "tree",
+                PhysicsParams {
+                    fcenter: 0.5,
+                    frepel: 20_000_000.,
+                    flink: 6.,
+                    link_length: 100.,
+                    ..defaults
+                },
+            ),
+        ];
+        Self {
+            presets: presets
+                .into_iter()
+                .map(|(name, params)| (name.to_string(), params))
+                .collect(),
+        }
+    }
+}
+
+/// Physics presets loaded from [`PhysicsPresets::path`]
+#[derive(Resource)]
+pub struct PhysicsPresets {
+    pub path: PathBuf,
+    file: PresetFile,
+    /// Name of the last applied preset, reapplied if the file changes
+    ///
+    /// Cleared when a parameter is edited by hand, so the edit is kept.
+    pub selected: Option<String>,
+    /// Modification time of the file when it was last loaded
+    loaded_at: Option<SystemTime>,
+    reload_timer: Timer,
+    /// Error from the last load or save, shown in the UI
+    pub error: Option<String>,
+    /// Name typed in the UI for saving the current parameters
+    pub name_input: String,
+}
+
+impl Default for PhysicsPresets {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRESETS_PATH)
+    }
+}
+
+impl PhysicsPresets {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: PresetFile::default(),
+            selected: None,
+            loaded_at: None,
+            reload_timer: Timer::new(RELOAD_INTERVAL, TimerMode::Repeating),
+            error: None,
+            name_input: String::new(),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.file.presets.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PhysicsParams> {
+        self.file.presets.get(name)
+    }
+
+    /// Applies the named preset to `gphysics` and remembers it as the selected one
+    pub fn apply(&mut self, name: &str, gphysics: &mut GlobalPhysics) -> Result<(), PresetError> {
+        let params = self
+            .get(name)
+            .ok_or_else(|| PresetError::NotFound(name.to_string()))?;
+        gphysics.set_params(params);
+        self.selected = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Stores `params` as the preset `name`, replacing any previous one, and writes the file
+    pub fn save_as(&mut self, name: &str, params: PhysicsParams) -> Result<(), PresetError> {
+        self.file.presets.insert(name.to_string(), params);
+        self.selected = Some(name.to_string());
+        self.write()
+    }
+
+    /// Writes all presets to the file, creating its directory if necessary
+    pub fn write(&mut self) -> Result<(), PresetError> {
+        let io_err = |source| PresetError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let text = ron::ser::to_string_pretty(&self.file, ron::ser::PrettyConfig::default())?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        fs::write(&self.path, text).map_err(io_err)?;
+        // Do not reload what was just written
+        self.loaded_at = modified_time(&self.path);
+        Ok(())
+    }
+
+    /// Reloads the file if it has changed since it was last loaded
+    ///
+    /// Returns true if the presets were reloaded. A missing file keeps the current presets.
+    pub fn reload_if_changed(&mut self) -> Result<bool, PresetError> {
+        let Some(modified) = modified_time(&self.path) else {
+            return Ok(false);
+        };
+        if self.loaded_at == Some(modified) {
+            return Ok(false);
+        }
+        // Only try each version of the file once, even if it fails to parse
+        self.loaded_at = Some(modified);
+
+        let text = fs::read_to_string(&self.path).map_err(|source| PresetError::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        self.file = ron::from_str(&text).map_err(|source| PresetError::Parse {
+            path: self.path.clone(),
+            source,
+        })?;
+        Ok(true)
+    }
+}
+
+pub(crate) fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Hot-reloads the presets file and reapplies the selected preset when it changes
+pub fn reload_presets(
+    time: Res<Time>,
+    mut presets: ResMut<PhysicsPresets>,
+    mut gphysics: ResMut<GlobalPhysics>,
+) {
+    if !presets.reload_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    match presets.reload_if_changed() {
+        Ok(true) => {
+            presets.error = None;
+            if let Some(name) = presets.selected.clone() {
+                if let Err(e) = presets.apply(&name, &mut gphysics) {
+                    presets.error = Some(e.to_string());
+                }
+            }
+        }
+        Ok(false) => {}
+        Err(e) => presets.error = Some(e.to_string()),
+    }
+}
@@ -0,0 +1,325 @@
+//! Visuals for the bubble graph
+//!
+//! The graph plugin spawns bubbles and connections without anything to draw. This plugin adds
+//! meshes, text and lines to them as they appear, and keeps the visuals in sync.
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    text::{BreakLineOn, Text2dBounds},
+    utils::{HashMap, HashSet},
+};
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    bubbles::{BubbleColor, BubbleContent, Bubbles, EdgeKind, BUBBLE_SIZE},
+    camera::MainCamera,
+    cursor_control::{CursorControl, InputMode},
+    debug_overlay::{draw_physics_gizmos, PhysicsDebug},
+    input::{BoxSelect, EdgeDrag},
+    physics::{link_physics, physics_system},
+    BubbleConnection, GraphBubble,
+};
+
+const EDGE_DRAG_COLOR: Color = Color::WHITE;
+const SELECTION_COLOR: Color = Color::rgb(0.6, 0.5, 0.);
+const FOCUS_COLOR: Color = Color::GOLD;
+const LINK_TARGET_COLOR: Color = Color::LIME_GREEN;
+
+/// Draws bubbles, connections and the physics debug overlay
+pub struct RenderingPlugin;
+
+impl Plugin for RenderingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ShapePlugin)
+            .init_resource::<BubbleAssets>()
+            .init_resource::<PhysicsDebug>()
+            .add_systems(Startup, spawn_origin_marker)
+            .add_systems(Update, (draw_edge_drag, draw_selection, draw_box_select))
+            .add_systems(
+                Update,
+                draw_physics_gizmos
+                    .after(physics_system)
+                    .after(link_physics),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    add_bubble_visuals,
+                    add_connection_visuals,
+                    sync_bubble_text,
+                    sync_bubble_color,
+                    sync_edge_kind,
+                ),
+            )
+            .add_systems(PostUpdate, update_links);
+    }
+}
+
+/// Mesh and materials shared by all bubbles
+#[derive(Resource)]
+struct BubbleAssets {
+    mesh: Mesh2dHandle,
+    /// Material of each bubble color, by [`Color::as_rgba_u32`]
+    materials: HashMap<u32, Handle<ColorMaterial>>,
+    /// All bubbles are scaled by this factor. Used for determining bubble shape.
+    base_scale: Vec2,
+}
+
+impl FromWorld for BubbleAssets {
+    fn from_world(world: &mut World) -> Self {
+        let (circle_mesh, base_scale) = make_scaled_circle(BUBBLE_SIZE);
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(circle_mesh).into();
+        Self {
+            mesh,
+            materials: HashMap::new(),
+            base_scale,
+        }
+    }
+}
+
+impl BubbleAssets {
+    /// Returns the material for `color`, creating it on first use
+    fn material(
+        &mut self,
+        color: Color,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> Handle<ColorMaterial> {
+        self.materials
+            .entry(color.as_rgba_u32())
+            .or_insert_with(|| materials.add(ColorMaterial::from(color)))
+            .clone()
+    }
+}
+
+fn spawn_origin_marker(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn(MaterialMesh2dBundle {
+        mesh: meshes.add(shape::Circle::new(5.).into()).into(),
+        material: materials.add(ColorMaterial::from(Color::RED)),
+        transform: Transform::from_translation(Vec3::new(0., 0., 2.)),
+        ..default()
+    });
+}
+
+/// Gives new bubbles an ellipse with their content as text on top
+///
+/// The material is set by [`sync_bubble_color`].
+fn add_bubble_visuals(
+    mut commands: Commands,
+    assets: Res<BubbleAssets>,
+    mut q: Query<(Entity, &BubbleContent, &mut Transform), Added<GraphBubble>>,
+) {
+    for (entity, content, mut tfm) in q.iter_mut() {
+        tfm.scale = assets.base_scale.extend(1.);
+        commands
+            .entity(entity)
+            .insert((assets.mesh.clone(), VisibilityBundle::default()))
+            .with_children(|builder| {
+                builder.spawn(Text2dBundle {
+                    text: create_text(&content.0),
+                    text_2d_bounds: Text2dBounds {
+                        // Wrap text in the rectangle
+                        size: biggest_rectangle_in_ellipse(BUBBLE_SIZE),
+                    },
+                    // Ensure the text is drawn on top
+                    transform: Transform::from_translation(Vec3::Z)
+                        .with_scale(Vec3::new(0.5, 1., 1.)),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+/// Width of connection lines
+const EDGE_WIDTH: f32 = 10.;
+
+/// Gives new connections a line, which is shaped by [`update_links`]
+fn add_connection_visuals(
+    mut commands: Commands,
+    q: Query<(Entity, &EdgeKind), Added<BubbleConnection>>,
+) {
+    for (entity, kind) in q.iter() {
+        commands.entity(entity).insert((
+            ShapeBundle::default(),
+            Fill::color(Color::CYAN),
+            Stroke::new(edge_color(*kind), EDGE_WIDTH),
+        ));
+    }
+}
+
+/// Recolors connections whose kind changed
+fn sync_edge_kind(mut q: Query<(&mut Stroke, &EdgeKind), Changed<EdgeKind>>) {
+    for (mut stroke, kind) in q.iter_mut() {
+        stroke.color = edge_color(*kind);
+    }
+}
+
+fn edge_color(kind: EdgeKind) -> Color {
+    match kind {
+        EdgeKind::Hierarchy => Color::BLACK,
+        EdgeKind::Reference => Color::rgb(0.27, 0.51, 0.71),
+        EdgeKind::Sequence => Color::ORANGE,
+    }
+}
+
+/// Sets the material of bubbles whose color changed
+fn sync_bubble_color(
+    mut commands: Commands,
+    mut assets: ResMut<BubbleAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    q: Query<(Entity, &BubbleColor), Changed<BubbleColor>>,
+) {
+    for (entity, color) in q.iter() {
+        let material = assets.material(color.0, &mut materials);
+        commands.entity(entity).insert(material);
+    }
+}
+
+/// Updates the text of bubbles whose content changed
+fn sync_bubble_text(
+    q: Query<(&BubbleContent, &Children), Changed<BubbleContent>>,
+    mut texts: Query<&mut Text>,
+) {
+    for (content, children) in q.iter() {
+        let mut iter = texts.iter_many_mut(children);
+        while let Some(mut text) = iter.fetch_next() {
+            text.sections[0].value = content.0.clone();
+        }
+    }
+}
+
+/// Draws a rubber band from the bubble an edge is dragged from to the cursor
+fn draw_edge_drag(
+    mut gizmos: Gizmos,
+    drag: Option<Res<EdgeDrag>>,
+    bubbles: Res<Bubbles>,
+    transforms: Query<&Transform, With<GraphBubble>>,
+) {
+    let Some(drag) = drag else {
+        return;
+    };
+    let Some(from) = drag
+        .from
+        .and_then(|id| transforms.get(bubbles.entity(id)?).ok())
+    else {
+        return;
+    };
+    gizmos.line_2d(from.translation.truncate(), drag.to, EDGE_DRAG_COLOR);
+    gizmos.circle_2d(drag.to, 5., EDGE_DRAG_COLOR);
+}
+
+/// Outlines the selected bubbles, and the link target with a line to it in link mode
+fn draw_selection(
+    mut gizmos: Gizmos,
+    control: Option<Res<CursorControl>>,
+    bubbles: Res<Bubbles>,
+    transforms: Query<&Transform, With<GraphBubble>>,
+) {
+    let Some(control) = control else {
+        return;
+    };
+    let position = |id| {
+        let tfm = transforms.get(bubbles.entity(id)?).ok()?;
+        Some(tfm.translation.truncate())
+    };
+    let outline = BUBBLE_SIZE * 2.2;
+
+    for selected in control.selected.iter().filter_map(|id| position(*id)) {
+        gizmos.rect_2d(selected, 0., outline, SELECTION_COLOR);
+    }
+    if let Some(focused) = control.focused.and_then(position) {
+        gizmos.rect_2d(focused, 0., outline * 1.1, FOCUS_COLOR);
+    }
+    if let InputMode::Link { from, to } = control.input_mode {
+        if let (Some(from), Some(to)) = (position(from), position(to)) {
+            gizmos.rect_2d(to, 0., outline, LINK_TARGET_COLOR);
+            gizmos.line_2d(from, to, LINK_TARGET_COLOR);
+        }
+    }
+}
+
+fn draw_box_select(mut gizmos: Gizmos, select: Option<Res<BoxSelect>>) {
+    if let Some(rect) = select.and_then(|select| select.rect()) {
+        gizmos.rect_2d(rect.center(), 0., rect.size(), SELECTION_COLOR);
+    }
+}
+
+/// Rebuilds the path of each visible connection whose endpoints moved
+///
+/// Connections outside the view are left stale until they become visible again.
+fn update_links(
+    mut links: Query<(Entity, &mut Path, &BubbleConnection)>,
+    transforms: Query<Ref<Transform>, With<GraphBubble>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    bubbles: Res<Bubbles>,
+    mut stale: Local<HashSet<Entity>>,
+) {
+    let endpoint = |id| transforms.get(bubbles.entity(id)?).ok();
+    let view = camera
+        .get_single()
+        .ok()
+        .and_then(|(camera, gtfm)| visible_world_rect(camera, gtfm));
+
+    for (entity, mut path, conn) in links.iter_mut() {
+        let (Some(left), Some(right)) = (endpoint(conn.0), endpoint(conn.1)) else {
+            continue;
+        };
+        let moved = path.is_added() || left.is_changed() || right.is_changed();
+        if !moved && !stale.contains(&entity) {
+            continue;
+        }
+
+        let (left, right) = (left.translation.truncate(), right.translation.truncate());
+        if view.is_some_and(|view| !overlaps(view, Rect::from_corners(left, right))) {
+            stale.insert(entity);
+            continue;
+        }
+        stale.remove(&entity);
+        *path = GeometryBuilder::build_as(&shapes::Line(left, right));
+    }
+
+    stale.retain(|entity| links.contains(*entity));
+}
+
+/// Part of the world shown by `camera`
+fn visible_world_rect(camera: &Camera, gtfm: &GlobalTransform) -> Option<Rect> {
+    let viewport = camera.logical_viewport_rect()?;
+    let min = camera.viewport_to_world_2d(gtfm, Vec2::ZERO)?;
+    let max = camera.viewport_to_world_2d(gtfm, viewport.size())?;
+    Some(Rect::from_corners(min, max))
+}
+
+/// True if the rects overlap, also for rects with zero width or height
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+}
+
+fn create_text(content: &str) -> Text {
+    let text_style = TextStyle {
+        font_size: 24.,
+        color: Color::WHITE,
+        ..Default::default()
+    };
+    Text {
+        sections: vec![TextSection::new(content, text_style)],
+        alignment: TextAlignment::Center,
+        linebreak_behavior: BreakLineOn::AnyCharacter,
+    }
+}
+
+/// Returns a circle mesh and a scaling vector
+fn make_scaled_circle(size: Vec2) -> (Mesh, Vec2) {
+    let (radius, scale) = if size.x < size.y {
+        (size.x, Vec2::new(1.0, size.y / size.x))
+    } else {
+        (size.y, Vec2::new(size.x / size.y, 1.0))
+    };
+    (shape::Circle::new(radius).into(), scale)
+}
+
+fn biggest_rectangle_in_ellipse(ellipse_size: Vec2) -> Vec2 {
+    Vec2::new(2f32.sqrt() * ellipse_size.x, 2f32.sqrt() * ellipse_size.y)
+}
@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, thread_rng, Error, Rng, RngCore, SeedableRng};
+
+/// Source of randomness for every random decision in bubble spawning and physics
+///
+/// The same seed makes the same random decisions, e.g., places new children in the same spots.
+/// That alone does not reproduce a layout, as the physics advances by the real frame time; a
+/// replayed session does, as it also repeats the time steps, see [`session`](crate::session).
+///
+/// The seed is not stored with the map yet, as maps cannot be saved, see SEED1 in
+/// `doc/agile/backlog.md`.
+#[derive(Resource)]
+pub struct SeededRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Default for SeededRng {
+    /// Picks a random seed. Use [`SeededRng::seed`] to find out which one.
+    fn default() -> Self {
+        Self::from_seed(thread_rng().gen())
+    }
+}
+
+impl SeededRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed this generator was last seeded with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the random sequence from `seed`
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::from_seed(seed);
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
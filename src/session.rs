@@ -0,0 +1,404 @@
+//! Session logs for reproducing what happened in a session
+//!
+//! The recorder writes the random seed and then one line per frame to a file: the length of the
+//! frame, the [`ControlEvent`]s sent, the [`BubbleCommand`]s applied and any change to the physics
+//! parameters. Each line is a RON [`SessionFrame`] and is written as soon as the frame ends, so
+//! the log is complete up to a crash. Replaying the log feeds the frames back with the same seed
+//! and the same time steps, which rebuilds the same graph in the same layout, with or without a
+//! window. Input devices, config file reloads and the side panels are paused during a replay, see
+//! [`LiveInput`], so nothing but the log changes the graph. Start the app with `--record <file>` or `--replay <file>`, see `main.rs`.
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{app::AppExit, prelude::*, time::TimeUpdateStrategy};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    bubbles::{BubbleCommand, BubbleEvent, Bubbles},
+    camera::ControlEvent,
+    physics::{GlobalPhysics, PhysicsParams},
+    rng::SeededRng,
+};
+
+/// Version of the log format, increased whenever old logs cannot be replayed anymore
+pub const SESSION_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("could not access {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("could not parse line {line} of {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        source: ron::error::SpannedError,
+    },
+    #[error("could not serialize session: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("{path} is empty")]
+    Empty { path: PathBuf },
+    #[error("{path} has version {found}, only version {SESSION_VERSION} can be replayed")]
+    Version { path: PathBuf, found: u32 },
+}
+
+/// First line of a session log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionHeader {
+    pub version: u32,
+    /// Seed of the [`SeededRng`] when the session started
+    pub seed: u64,
+}
+
+/// Everything recorded in one frame, a line of a session log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionFrame {
+    /// Number of the frame, counting from 0
+    pub frame: u64,
+    /// Seconds since the session started, at the start of the frame
+    pub time: f64,
+    /// Length of the frame in nanoseconds, for replaying it with the same time step
+    pub delta_nanos: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub control: Vec<ControlEvent>,
+    /// Commands read by [`apply_bubble_commands`](crate::bubbles::apply_bubble_commands) in the
+    /// frame, in order, including those that failed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<BubbleCommand>,
+    /// The physics parameters, if they changed in the frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physics: Option<PhysicsParams>,
+}
+
+/// Records or replays a session, depending on which of [`SessionRecorder`] and [`SessionReplay`]
+/// is inserted
+///
+/// Requires [`GraphPlugin`](crate::bubbles::GraphPlugin) and
+/// [`PhysicsPlugin`](crate::physics::PhysicsPlugin).
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ControlEvent>()
+            .add_event::<BubbleCommand>()
+            .configure_set(
+                Update,
+                LiveInput.run_if(not(resource_exists::<SessionReplay>())),
+            )
+            .add_systems(PreUpdate, replay_session)
+            .add_systems(Last, record_session);
+    }
+}
+
+/// Systems that read input devices or reload config files
+///
+/// They are paused while a [`SessionReplay`] runs, so that only the recorded events change the
+/// graph, the view and the physics.
+#[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LiveInput;
+
+/// Writes a session log, see the [module docs](self)
+#[derive(Resource)]
+pub struct SessionRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    frame: u64,
+    /// Physics parameters last written, to write only changes
+    physics: Option<PhysicsParams>,
+    /// Set when writing failed, which stops the recording
+    pub error: Option<String>,
+}
+
+impl SessionRecorder {
+    /// Creates the log file, replacing any previous one, and its directory if necessary
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self, SessionError> {
+        let path = path.into();
+        let io_err = |source| SessionError::Io {
+            path: path.clone(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        let file = File::create(&path).map_err(io_err)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            path,
+            frame: 0,
+            physics: None,
+            error: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> Result<(), SessionError> {
+        let line = ron::to_string(value)?;
+        let io_err = |source| SessionError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        writeln!(self.writer, "{line}").map_err(io_err)?;
+        // Keep the log complete in case the app crashes
+        self.writer.flush().map_err(io_err)
+    }
+}
+
+/// Writes the events of this frame to the session log, if recording
+pub fn record_session(
+    recorder: Option<ResMut<SessionRecorder>>,
+    time: Res<Time>,
+    (rng, gphysics): (Res<SeededRng>, Option<Res<GlobalPhysics>>),
+    mut control_events: EventReader<ControlEvent>,
+    mut bubble_events: EventReader<BubbleEvent>,
+) {
+    let Some(mut recorder) = recorder.filter(|recorder| recorder.error.is_none()) else {
+        return;
+    };
+
+    let physics = gphysics
+        .map(|gphysics| gphysics.params())
+        .filter(|params| recorder.physics.as_ref() != Some(params));
+    let frame = SessionFrame {
+        frame: recorder.frame,
+        time: (time.elapsed() - time.delta()).as_secs_f64(),
+        delta_nanos: time.delta().as_nanos() as u64,
+        control: control_events.iter().cloned().collect(),
+        commands: bubble_events
+            .iter()
+            .filter_map(|ev| match ev {
+                BubbleEvent::Applied(command) | BubbleEvent::Failed { command, .. } => {
+                    Some(command.clone())
+                }
+                BubbleEvent::Spawned { .. } => None,
+            })
+            .collect(),
+        physics: physics.clone(),
+    };
+
+    let mut result = Ok(());
+    if recorder.frame == 0 {
+        let header = SessionHeader {
+            version: SESSION_VERSION,
+            seed: rng.seed(),
+        };
+        result = recorder.write_line(&header);
+        info!("Recording session to {}", recorder.path.display());
+    }
+    result = result.and_then(|()| recorder.write_line(&frame));
+    if let Err(e) = result {
+        error!("Stopped recording the session: {e}");
+        recorder.error = Some(e.to_string());
+    }
+    recorder.frame += 1;
+    if physics.is_some() {
+        recorder.physics = physics;
+    }
+}
+
+/// A session log being replayed, see the [module docs](self)
+#[derive(Resource)]
+pub struct SessionReplay {
+    pub header: SessionHeader,
+    /// Frames that have not been replayed yet
+    frames: VecDeque<SessionFrame>,
+    frame: u64,
+    /// Exit the app when the log has been replayed, e.g., when running without a window
+    pub exit_when_done: bool,
+}
+
+impl SessionReplay {
+    /// Reads a session log
+    ///
+    /// A last line that cannot be parsed is dropped with a warning, as it was probably cut short
+    /// by a crash.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SessionError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| SessionError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let parse_err = |line, source| SessionError::Parse {
+            path: path.to_path_buf(),
+            line,
+            source,
+        };
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+
+        let (idx, first) = lines.next().ok_or_else(|| SessionError::Empty {
+            path: path.to_path_buf(),
+        })?;
+        let header: SessionHeader = ron::from_str(first).map_err(|e| parse_err(idx + 1, e))?;
+        if header.version != SESSION_VERSION {
+            return Err(SessionError::Version {
+                path: path.to_path_buf(),
+                found: header.version,
+            });
+        }
+
+        let lines: Vec<_> = lines.collect();
+        let mut frames = VecDeque::with_capacity(lines.len());
+        for (pos, (idx, line)) in lines.iter().enumerate() {
+            match ron::from_str(line) {
+                Ok(frame) => frames.push_back(frame),
+                Err(e) if pos + 1 == lines.len() => {
+                    warn!("Dropping the last line of {}: {e}", path.display());
+                }
+                Err(e) => return Err(parse_err(idx + 1, e)),
+            }
+        }
+        Ok(Self {
+            header,
+            frames,
+            frame: 0,
+            exit_when_done: false,
+        })
+    }
+
+    /// The time step of the first frame, for [`TimeUpdateStrategy::ManualDuration`]
+    pub fn first_delta(&self) -> Duration {
+        self.frames.front().map_or(Duration::ZERO, |frame| {
+            Duration::from_nanos(frame.delta_nanos)
+        })
+    }
+
+    /// Inserts the replay with the recorded seed and the time step of the first frame
+    pub fn insert_into(self, app: &mut App) {
+        app.insert_resource(SeededRng::from_seed(self.header.seed))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(self.first_delta()))
+            .insert_resource(self);
+    }
+}
+
+/// Sends the recorded events of this frame and sets up the time step of the next one
+///
+/// When all frames have been replayed, time runs normally again and the app exits if
+/// [`SessionReplay::exit_when_done`] is set.
+pub fn replay_session(
+    mut commands: Commands,
+    replay: Option<ResMut<SessionReplay>>,
+    (mut time_strategy, gphysics): (ResMut<TimeUpdateStrategy>, Option<ResMut<GlobalPhysics>>),
+    mut control_events: EventWriter<ControlEvent>,
+    mut bubble_commands: EventWriter<BubbleCommand>,
+    bubbles: Res<Bubbles>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+
+    if replay
+        .frames
+        .front()
+        .is_some_and(|f| f.frame == replay.frame)
+    {
+        let frame = replay.frames.pop_front().unwrap();
+        if let (Some(params), Some(mut gphysics)) = (&frame.physics, gphysics) {
+            gphysics.set_params(params);
+        }
+        // Bulk operations, changes and edits are replayed by the commands they caused
+        control_events.send_batch(frame.control.into_iter().filter(|ev| {
+            !matches!(
+                ev,
+                ControlEvent::Bulk(_) | ControlEvent::Change(_) | ControlEvent::Edit(_)
+            )
+        }));
+        bubble_commands.send_batch(frame.commands);
+    }
+    replay.frame += 1;
+
+    match replay.frames.front() {
+        Some(next) => {
+            *time_strategy =
+                TimeUpdateStrategy::ManualDuration(Duration::from_nanos(next.delta_nanos));
+        }
+        None => {
+            info!(
+                "Replayed {} frames: {} bubbles, {} edges",
+                replay.frame,
+                bubbles.ids().len(),
+                bubbles.edges().len()
+            );
+            *time_strategy = TimeUpdateStrategy::Automatic;
+            if replay.exit_when_done {
+                exit.send(AppExit);
+            }
+            commands.remove_resource::<SessionReplay>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bubbles::BubbleId, headless_app, GraphBubble};
+
+    type Snapshot = (Vec<(BubbleId, BubbleId)>, Vec<(BubbleId, Vec2)>);
+
+    /// Edges and positions of every bubble, sorted by id
+    fn snapshot(app: &mut App) -> Snapshot {
+        let mut edges = app.world.resource::<Bubbles>().edges();
+        edges.sort_unstable();
+        let mut positions = app
+            .world
+            .query::<(&GraphBubble, &Transform)>()
+            .iter(&app.world)
+            .map(|(id, tfm)| (id.0, tfm.translation.truncate()))
+            .collect::<Vec<_>>();
+        positions.sort_unstable_by_key(|(id, _)| *id);
+        (edges, positions)
+    }
+
+    #[test]
+    fn replay_rebuilds_the_recorded_graph() {
+        let path = std::env::temp_dir().join(format!("zeal-session-{}.ron", std::process::id()));
+
+        let mut app = headless_app();
+        app.add_plugins(SessionPlugin)
+            .insert_resource(SessionRecorder::create(&path).unwrap())
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                16,
+            )));
+        let script = [
+            vec![BubbleCommand::SpawnOrphan(Vec2::ZERO)],
+            vec![BubbleCommand::SpawnChild(0), BubbleCommand::SpawnChild(0)],
+            vec![BubbleCommand::SpawnChild(1), BubbleCommand::Connect(2, 1)],
+            vec![BubbleCommand::SetContent(3, "hub".repeat(10))],
+        ];
+        for commands in script {
+            app.world.send_event_batch(commands);
+            app.update();
+        }
+        app.world.resource_mut::<GlobalPhysics>().frepel *= 2.;
+        for _ in 0..30 {
+            app.update();
+        }
+        let recorded = snapshot(&mut app);
+        // Dropping the recorder closes the log
+        drop(app);
+
+        let mut app = headless_app();
+        app.add_plugins(SessionPlugin);
+        SessionReplay::load(&path).unwrap().insert_into(&mut app);
+        let mut frames = 0;
+        while app.world.contains_resource::<SessionReplay>() {
+            app.update();
+            frames += 1;
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(frames, 34);
+        assert_eq!(recorded.0.len(), 4);
+        assert_eq!(snapshot(&mut app), recorded);
+    }
+}
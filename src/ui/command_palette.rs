@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    cursor_control::CursorControl,
+    input::{format_sequence, Action, ActionEvent, CommandPalette, Keymap},
+};
+
+/// Draws the open [`CommandPalette`] and sends the [`Action`] picked from it
+///
+/// Actions that do nothing in the current mode are listed but disabled.
+pub fn command_palette_ui(
+    mut contexts: EguiContexts,
+    mut palette: ResMut<CommandPalette>,
+    keymap: Res<Keymap>,
+    control: Res<CursorControl>,
+    mut actions: EventWriter<ActionEvent>,
+) {
+    if !palette.open {
+        return;
+    }
+    let mode = control.input_mode.as_str();
+    let entries = matching_actions(&palette.query);
+    let available = |action: &Action| action.modes().contains(&mode);
+
+    let mut picked = None;
+    egui::Window::new("Commands")
+        .title_bar(false)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, [0., 60.])
+        .show(contexts.ctx_mut(), |ui| {
+            let edit = ui.add(egui::TextEdit::singleline(&mut palette.query).hint_text("Search"));
+            edit.request_focus();
+            if edit.changed() {
+                palette.selected = 0;
+            }
+
+            let (up, down, enter, escape) = ui.input(|i| {
+                (
+                    i.key_pressed(egui::Key::ArrowUp),
+                    i.key_pressed(egui::Key::ArrowDown),
+                    i.key_pressed(egui::Key::Enter),
+                    i.key_pressed(egui::Key::Escape),
+                )
+            });
+            if escape {
+                palette.close();
+                return;
+            }
+            let last = entries.len().saturating_sub(1);
+            if up {
+                palette.selected = palette.selected.saturating_sub(1);
+            }
+            if down {
+                palette.selected = (palette.selected + 1).min(last);
+            }
+            if enter {
+                picked = entries
+                    .get(palette.selected)
+                    .filter(|action| available(action))
+                    .copied();
+            }
+
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    for (idx, action) in entries.iter().enumerate() {
+                        let text = format!("{}: {}", action.category(), action.label());
+                        let highlighted = idx == palette.selected;
+                        let response = ui
+                            .horizontal(|ui| {
+                                let response = ui.add_enabled(
+                                    available(action),
+                                    egui::SelectableLabel::new(highlighted, text),
+                                );
+                                ui.weak(binding_text(&keymap, mode, *action));
+                                response
+                            })
+                            .inner;
+                        if response.clicked() {
+                            picked = Some(*action);
+                        }
+                        if highlighted && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                    }
+                });
+        });
+
+    if let Some(action) = picked {
+        palette.close();
+        actions.send(action.into());
+    }
+}
+
+/// Actions that can be run from the palette, best matches of `query` first
+fn matching_actions(query: &str) -> Vec<Action> {
+    let mut scored = Action::all()
+        .into_iter()
+        // Held actions only last for a single frame when run from the palette
+        .filter(|action| !action.is_held())
+        .filter_map(|action| {
+            let text = format!("{} {}", action.category(), action.label());
+            fuzzy_score(query, &text).map(|score| (action, score))
+        })
+        .collect::<Vec<_>>();
+    // Stable, so equally good matches stay in the order of `Action::all`
+    scored.sort_by_key(|(_, score)| -score);
+    scored.into_iter().map(|(action, _)| action).collect()
+}
+
+/// Keys bound to `action` in `mode`, or in the first mode it is available in
+fn binding_text(keymap: &Keymap, mode: &str, action: Action) -> String {
+    let join = |mode| {
+        keymap
+            .sequences_of(mode, action)
+            .map(format_sequence)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if action.modes().contains(&mode) {
+        return join(mode);
+    }
+    match action.modes().first() {
+        Some(other) => {
+            let keys = join(other);
+            if keys.is_empty() {
+                format!("in {other} mode")
+            } else {
+                format!("{keys} in {other} mode")
+            }
+        }
+        None => String::new(),
+    }
+}
+
+/// Scores how well `query` matches `text` as a subsequence, ignoring case and whitespace in the
+/// query, or returns `None` if it does not match
+///
+/// Runs of consecutive characters and characters at the start of words score higher, so "dsel"
+/// ranks "delete selection" above "delete focused bubble".
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut next = 0;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let idx = (next..text.len()).find(|idx| text[*idx] == c)?;
+        score += 1;
+        if idx > 0 && idx == next {
+            score += 4;
+        }
+        if idx == 0 || !text[idx - 1].is_alphanumeric() {
+            score += 3;
+        }
+        next = idx + 1;
+    }
+    Some(score)
+}
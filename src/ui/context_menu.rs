@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::palette_ui;
+use crate::{
+    bubbles::{BubbleCommand, BubbleId, Bubbles, EdgeId, EdgeKind},
+    camera::ControlEvent,
+    input::{ContextMenu, MenuTarget},
+    physics::BubblePhysics,
+};
+
+/// Draws the open [`ContextMenu`] and sends the command picked from it
+pub fn context_menu_ui(
+    mut contexts: EguiContexts,
+    mut menu: ResMut<ContextMenu>,
+    bubbles: Res<Bubbles>,
+    physics: Query<&BubblePhysics>,
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    let Some(target) = menu.target else {
+        return;
+    };
+    let pos = egui::pos2(menu.screen_pos.x, menu.screen_pos.y);
+
+    let mut picked = false;
+    egui::Area::new("context_menu")
+        .fixed_pos(pos)
+        .order(egui::Order::Foreground)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                let mut send = |ev| {
+                    control_events.send(ev);
+                    picked = true;
+                };
+                match target {
+                    MenuTarget::Bubble(id) => {
+                        let pinned = bubbles
+                            .entity(id)
+                            .and_then(|e| physics.get(e).ok())
+                            .is_some_and(|physics| physics.pinned);
+                        bubble_menu(ui, id, pinned, &mut send);
+                        if ui.button("Select").clicked() {
+                            send(ControlEvent::Select(id));
+                        }
+                    }
+                    MenuTarget::Edge { edge, from, to } => {
+                        // The edge was removed or reversed since the menu was opened, and its ID
+                        // may now belong to another edge
+                        if bubbles.edge_endpoints(edge).ok() != Some((from, to)) {
+                            picked = true;
+                            return;
+                        }
+                        edge_menu(ui, (edge, from, to), &bubbles, &mut send);
+                    }
+                    MenuTarget::Canvas => {
+                        if ui.button("New bubble here").clicked() {
+                            send(ControlEvent::Edit(BubbleCommand::SpawnOrphan(
+                                menu.world_pos,
+                            )));
+                        }
+                        if ui.button("Clear selection").clicked() {
+                            send(ControlEvent::ClearSelection);
+                        }
+                    }
+                }
+            });
+        });
+
+    if picked {
+        menu.close();
+    }
+}
+
+fn bubble_menu(ui: &mut egui::Ui, id: BubbleId, pinned: bool, send: &mut impl FnMut(ControlEvent)) {
+    let mut send = |command| send(ControlEvent::Edit(command));
+    ui.label(format!("Bubble {id}"));
+    if ui.button("Add child").clicked() {
+        send(BubbleCommand::SpawnChild(id));
+    }
+    if ui.button(if pinned { "Unpin" } else { "Pin" }).clicked() {
+        send(BubbleCommand::SetPinned(id, !pinned));
+    }
+    if ui.button("Delete").clicked() {
+        send(BubbleCommand::Remove(id));
+    }
+    if let Some(color) = palette_ui(ui) {
+        send(BubbleCommand::SetColor(id, color));
+    }
+}
+
+fn edge_menu(
+    ui: &mut egui::Ui,
+    (edge, from, to): (EdgeId, BubbleId, BubbleId),
+    bubbles: &Bubbles,
+    send: &mut impl FnMut(ControlEvent),
+) {
+    let mut send = |command| send(ControlEvent::Edit(command));
+    ui.label(format!("Edge {from} -> {to}"));
+    let current = bubbles.edge_kind(edge).ok();
+    ui.horizontal(|ui| {
+        for kind in EdgeKind::ALL {
+            if ui
+                .selectable_label(current == Some(kind), kind.as_str())
+                .clicked()
+            {
+                send(BubbleCommand::SetEdgeKind(edge, kind));
+            }
+        }
+    });
+    if ui.button("Reverse").clicked() {
+        send(BubbleCommand::ReverseEdge(edge));
+    }
+    if ui.button("Remove").clicked() {
+        send(BubbleCommand::RemoveEdge(edge));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::input::{format_sequence, Keymap, PendingKeys, PixelScroll};
+use bevy_egui::egui::{self, CollapsingHeader};
+
+/// Lists the bindings of `mode` and the problems with the keymap file
+pub fn keymap_ui(ui: &mut egui::Ui, keymap: &mut Keymap, pending: &PendingKeys, mode: &str) {
+    let header = if keymap.problems.is_empty() && keymap.error.is_none() {
+        "Keys".to_string()
+    } else {
+        format!(
+            "Keys ({} problems)",
+            keymap.problems.len() + keymap.error.iter().count()
+        )
+    };
+    CollapsingHeader::new(header)
+        .default_open(false)
+        .show(ui, |ui| {
+            if !pending.is_empty() {
+                let count = pending.count.map(|count| count.to_string());
+                let keys = count.into_iter().chain([format_sequence(&pending.chords)]);
+                ui.label(format!(
+                    "Pending: {} ...",
+                    keys.collect::<Vec<_>>().join(" ")
+                ));
+            }
+            egui::Grid::new("key_bindings").show(ui, |ui| {
+                for (chords, action) in keymap.bindings(mode) {
+                    ui.monospace(format_sequence(chords));
+                    ui.label(format!("{action:?}"));
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Pixel scrolling");
+                let pixel_scroll = keymap.pixel_scroll_mut();
+                ui.radio_value(pixel_scroll, PixelScroll::Pan, "pans")
+                    .on_hover_text("For trackpads, which zoom by pinching");
+                ui.radio_value(pixel_scroll, PixelScroll::Zoom, "zooms")
+                    .on_hover_text("For mice with smooth wheels");
+            });
+
+            ui.separator();
+            ui.label("Gamepad");
+            let gamepad = keymap.gamepad_mut();
+            ui.add(egui::Slider::new(&mut gamepad.dead_zone, 0.0..=0.9).text("Dead zone"));
+            ui.add(
+                egui::Slider::new(&mut gamepad.pan_sensitivity, 50.0..=3000.0)
+                    .logarithmic(true)
+                    .text("Pan speed"),
+            );
+            ui.add(
+                egui::Slider::new(&mut gamepad.zoom_sensitivity, 0.1..=10.0)
+                    .logarithmic(true)
+                    .text("Zoom speed"),
+            );
+
+            ui.separator();
+            ui.label(format!("Loaded from {}", keymap.path.display()));
+            if ui
+                .button("Save")
+                .on_hover_text("Write the current keymap to the file, for editing it")
+                .clicked()
+            {
+                keymap.error = keymap.write().err().map(|e| e.to_string());
+            }
+            let error_color = ui.visuals().error_fg_color;
+            if let Some(e) = &keymap.error {
+                ui.colored_label(error_color, e);
+            }
+            for problem in &keymap.problems {
+                ui.colored_label(error_color, problem);
+            }
+        });
+}
@@ -0,0 +1,89 @@
+use crate::{cursor_control::CursorControl, input::Macros};
+use bevy_egui::egui::{self, CollapsingHeader};
+
+/// Recording, selecting, playing and deleting macros
+pub fn macros_ui(ui: &mut egui::Ui, macros: &mut Macros, control: &CursorControl) {
+    let header = match macros.recorded_steps() {
+        Some(_) => "Macros (recording)",
+        None => "Macros",
+    };
+    CollapsingHeader::new(header)
+        .default_open(false)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut macros.name_input)
+                    .on_hover_text("Name of the next recording");
+                let result = match macros.recorded_steps() {
+                    None => ui
+                        .button("Record")
+                        .on_hover_text("Record changes relative to the focused bubble")
+                        .clicked()
+                        .then(|| macros.start_recording(control.focused)),
+                    Some(steps) => {
+                        let stopped = ui
+                            .button(format!("Stop ({steps} steps)"))
+                            .on_hover_text("Save the recording under the name")
+                            .clicked()
+                            .then(|| macros.toggle_recording(control.focused));
+                        if ui.button("Cancel").clicked() {
+                            macros.cancel_recording();
+                        }
+                        stopped
+                    }
+                };
+                if let Some(result) = result {
+                    macros.error = result.err().map(|e| e.to_string());
+                }
+            });
+
+            let mut clicked = None;
+            for name in macros.names() {
+                let steps = macros.get(name).map_or(0, |m| m.steps.len());
+                if ui
+                    .selectable_label(
+                        macros.selected.as_deref() == Some(name),
+                        format!("{name} ({steps} steps)"),
+                    )
+                    .clicked()
+                {
+                    clicked = Some(name.to_string());
+                }
+            }
+            if clicked.is_some() {
+                macros.selected = clicked;
+            }
+
+            if let Some(name) = macros.selected.clone() {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut macros.count)
+                            .clamp_range(1..=99)
+                            .suffix("x"),
+                    );
+                    let playable = macros.playing().is_none() && macros.recorded_steps().is_none();
+                    let result = if ui
+                        .add_enabled(playable, egui::Button::new("Play"))
+                        .on_hover_text("Play on each selected bubble")
+                        .clicked()
+                    {
+                        Some(macros.play(&name, macros.count, control))
+                    } else if ui.button("Delete").clicked() {
+                        Some(macros.remove(&name))
+                    } else {
+                        None
+                    };
+                    if let Some(result) = result {
+                        macros.error = result.err().map(|e| e.to_string());
+                    }
+                });
+            }
+            if let Some(name) = macros.playing() {
+                ui.label(format!("Playing {name} ..."));
+            }
+
+            ui.label(format!("Stored in {}", macros.path.display()));
+            if let Some(e) = &macros.error {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+        });
+}
@@ -1,18 +1,68 @@
+mod command_palette;
+mod context_menu;
+mod keymap;
+mod macros;
 mod physics_config;
+mod physics_debug;
 
-use self::physics_config::physics_config_ui;
+use self::{
+    command_palette::command_palette_ui, context_menu::context_menu_ui, keymap::keymap_ui,
+    macros::macros_ui, physics_config::physics_config_ui, physics_debug::physics_debug_ui,
+};
 use crate::{
+    bubbles::{BubbleCommand, BubbleEvent},
     camera::ControlEvent,
-    cursor_control::{CursorControl, InputMode},
+    cursor_control::{BulkOp, CursorControl, InputMode},
+    debug_overlay::{PhysicsDebug, PhysicsStats},
+    input::{ActionEvent, CommandPalette, ContextMenu, Keymap, Macros, PendingKeys, PALETTE},
     physics::GlobalPhysics,
+    presets::PhysicsPresets,
+    session::SessionReplay,
 };
 use bevy::prelude::*;
 use bevy_egui::{
     egui::{self, CollapsingHeader},
-    EguiContexts,
+    EguiContexts, EguiPlugin,
 };
 use std::collections::VecDeque;
 
+/// The egui panels around the map
+///
+/// Requires [`PhysicsPlugin`](crate::physics::PhysicsPlugin).
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.add_event::<ControlEvent>()
+            .add_event::<BubbleEvent>()
+            .add_event::<BubbleCommand>()
+            .add_event::<ActionEvent>()
+            .init_resource::<CursorControl>()
+            .init_resource::<ContextMenu>()
+            .init_resource::<CommandPalette>()
+            .init_resource::<Keymap>()
+            .init_resource::<PendingKeys>()
+            .init_resource::<Macros>()
+            .init_resource::<GraphErrors>()
+            .init_resource::<OccupiedScreenSpace>()
+            .init_resource::<ControlHistory>()
+            .init_resource::<PhysicsDebug>()
+            // Systems that create Egui widgets should be run during the `CoreSet::Update` set,
+            // or after the `EguiSet::BeginFrame` system (which belongs to the `CoreSet::PreUpdate` set).
+            .add_systems(
+                Update,
+                (
+                    ui_system,
+                    (context_menu_ui, command_palette_ui).after(ui_system),
+                ),
+            )
+            .add_systems(Update, (record_command_history, record_graph_errors));
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct OccupiedScreenSpace {
     pub left: f32,
@@ -30,27 +80,92 @@ fn capitalize(s: &str) -> String {
     }
 }
 
+fn record_command_history(
+    mut commands: EventReader<ControlEvent>,
+    mut history: ResMut<ControlHistory>,
+) {
+    let len = commands.len();
+    history.extend_with_len(commands.iter().cloned(), len);
+}
+
+/// Keeps the errors from editing the graph for showing them to the user
+fn record_graph_errors(mut events: EventReader<BubbleEvent>, mut errors: ResMut<GraphErrors>) {
+    for ev in events.iter() {
+        if let BubbleEvent::Failed { command, error } = ev {
+            errors.push(format!("{command:?}: {error}"));
+        }
+    }
+}
+
 pub fn ui_system(
     mut contexts: EguiContexts,
     mut occupied_screen_space: ResMut<OccupiedScreenSpace>,
-    cursor_control: Res<CursorControl>,
+    (cursor_control, mut control_events): (Res<CursorControl>, EventWriter<ControlEvent>),
     mut gphysics: ResMut<GlobalPhysics>,
-    history: Res<ControlHistory>,
+    (history, mut errors): (Res<ControlHistory>, ResMut<GraphErrors>),
+    (mut presets, mut keymap, pending, mut macros): (
+        ResMut<PhysicsPresets>,
+        ResMut<Keymap>,
+        Res<PendingKeys>,
+        ResMut<Macros>,
+    ),
+    (mut debug, stats, replay): (
+        ResMut<PhysicsDebug>,
+        Res<PhysicsStats>,
+        Option<Res<SessionReplay>>,
+    ),
 ) {
     let ctx = contexts.ctx_mut();
+    // Only the recorded events may change anything while a session is replayed
+    let editable = replay.is_none();
 
-    occupied_screen_space.left = left_panel(ctx, &cursor_control.input_mode);
-    occupied_screen_space.right = right_panel(ctx, &mut gphysics, &history);
+    occupied_screen_space.left = left_panel(
+        ctx,
+        &cursor_control,
+        &mut control_events,
+        (&mut keymap, &pending),
+        &mut macros,
+        editable,
+    );
+    occupied_screen_space.right = right_panel(
+        ctx,
+        editable,
+        &mut gphysics,
+        &mut presets,
+        &mut debug,
+        &stats,
+        &history,
+    );
     occupied_screen_space.top = top_panel(ctx);
-    occupied_screen_space.bottom = bottom_panel(ctx);
+    occupied_screen_space.bottom = bottom_panel(ctx, &mut errors);
 }
 
-fn left_panel(ctx: &mut egui::Context, input_mode: &InputMode) -> f32 {
+fn left_panel(
+    ctx: &mut egui::Context,
+    control: &CursorControl,
+    control_events: &mut EventWriter<ControlEvent>,
+    (keymap, pending): (&mut Keymap, &PendingKeys),
+    macros: &mut Macros,
+    editable: bool,
+) -> f32 {
     egui::SidePanel::left("left_panel")
         .resizable(true)
         .show(ctx, |ui| {
+            let input_mode = &control.input_mode;
             ui.label("Left resizeable panel");
+            if !editable {
+                ui.label("Replaying a session, input is paused");
+            }
             ui.label(format!("Input mode: {}", capitalize(input_mode.as_str())));
+            if let InputMode::Link { from, to } = input_mode {
+                ui.label(format!("Linking {to} as a child of {from}"));
+                ui.label("Arrows: pick, Enter: connect, Esc: cancel");
+            }
+            ui.add_enabled_ui(editable, |ui| {
+                selection_ui(ui, control, control_events);
+                keymap_ui(ui, keymap, pending, input_mode.as_str());
+                macros_ui(ui, macros, control);
+            });
             ui.allocate_rect(ui.available_rect_before_wrap(), egui::Sense::hover());
         })
         .response
@@ -58,9 +173,64 @@ fn left_panel(ctx: &mut egui::Context, input_mode: &InputMode) -> f32 {
         .width()
 }
 
+fn selection_ui(
+    ui: &mut egui::Ui,
+    control: &CursorControl,
+    control_events: &mut EventWriter<ControlEvent>,
+) {
+    CollapsingHeader::new(format!("Selection ({})", control.selected.len()))
+        .default_open(true)
+        .show(ui, |ui| {
+            if control.selected.is_empty() {
+                ui.label("Click a bubble to select it, shift to select many");
+                return;
+            }
+            let mut bulk = |op| control_events.send(ControlEvent::Bulk(op));
+            ui.horizontal(|ui| {
+                if ui.button("Delete").clicked() {
+                    bulk(BulkOp::Delete);
+                }
+                if ui.button("Group").clicked() {
+                    bulk(BulkOp::Group);
+                }
+            });
+            if let Some(focused) = control.focused {
+                if ui.button(format!("Connect all to {focused}")).clicked() {
+                    bulk(BulkOp::ConnectToFocused);
+                }
+            }
+            if let Some(color) = palette_ui(ui) {
+                bulk(BulkOp::SetColor(color));
+            }
+            if ui.button("Clear selection").clicked() {
+                control_events.send(ControlEvent::ClearSelection);
+            }
+        });
+}
+
+/// A row of color swatches, returns the color that was clicked
+fn palette_ui(ui: &mut egui::Ui) -> Option<Color> {
+    ui.horizontal(|ui| {
+        let mut clicked = None;
+        for color in PALETTE {
+            let [r, g, b, _] = color.as_rgba_u8();
+            let swatch = egui::Button::new("  ").fill(egui::Color32::from_rgb(r, g, b));
+            if ui.add(swatch).clicked() {
+                clicked = Some(color);
+            }
+        }
+        clicked
+    })
+    .inner
+}
+
 fn right_panel(
     ctx: &mut egui::Context,
+    editable: bool,
     gphysics: &mut GlobalPhysics,
+    presets: &mut PhysicsPresets,
+    debug: &mut PhysicsDebug,
+    stats: &PhysicsStats,
     history: &ControlHistory,
 ) -> f32 {
     egui::SidePanel::right("right_panel")
@@ -69,7 +239,8 @@ fn right_panel(
             ui.label("Right resizeable panel");
             ui.horizontal(|ui| {
                 ui.vertical(|ui| {
-                    physics_config_ui(ui, gphysics);
+                    ui.add_enabled_ui(editable, |ui| physics_config_ui(ui, gphysics, presets));
+                    physics_debug_ui(ui, gphysics, debug, stats);
                     input_event_log_ui(ui, history);
                 })
             });
@@ -92,11 +263,12 @@ fn top_panel(ctx: &mut egui::Context) -> f32 {
         .height()
 }
 
-fn bottom_panel(ctx: &mut egui::Context) -> f32 {
+fn bottom_panel(ctx: &mut egui::Context, errors: &mut GraphErrors) -> f32 {
     egui::TopBottomPanel::bottom("bottom_panel")
         .resizable(true)
         .show(ctx, |ui| {
             ui.label("Bottom resizeable panel");
+            graph_errors_ui(ui, errors);
             ui.allocate_rect(ui.available_rect_before_wrap(), egui::Sense::hover());
         })
         .response
@@ -104,6 +276,50 @@ fn bottom_panel(ctx: &mut egui::Context) -> f32 {
         .height()
 }
 
+/// Most recent errors from editing the graph, newest last
+#[derive(Resource)]
+pub struct GraphErrors {
+    messages: VecDeque<String>,
+    max_len: usize,
+}
+
+impl Default for GraphErrors {
+    fn default() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            max_len: 8,
+        }
+    }
+}
+
+impl GraphErrors {
+    pub fn push(&mut self, message: String) {
+        if self.messages.len() == self.max_len {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
+
+fn graph_errors_ui(ui: &mut egui::Ui, errors: &mut GraphErrors) {
+    if errors.messages.is_empty() {
+        return;
+    }
+    ui.horizontal(|ui| {
+        ui.colored_label(egui::Color32::RED, "Errors");
+        if ui.button("Clear").clicked() {
+            errors.clear();
+        }
+    });
+    for message in errors.messages.iter().rev() {
+        ui.colored_label(egui::Color32::RED, message);
+    }
+}
+
 #[derive(Resource)]
 pub struct ControlHistory {
     // TODO: an actual cyclic buffer may be more efficient
@@ -152,7 +368,9 @@ pub fn input_event_log_ui(ui: &mut egui::Ui, history: &ControlHistory) {
         });
 }
 
-/// Merges a list of events by collating events of the same type into single events
+/// Merges a list of events by collating consecutive pans and zooms into single events
+///
+/// Mode changes are kept apart so that each step, e.g., of picking a link target, is listed.
 fn merge_similar_events<'a>(
     it: impl Iterator<Item = &'a ControlEvent> + Clone,
 ) -> Vec<ControlEvent> {
@@ -168,9 +386,6 @@ fn merge_similar_events<'a>(
                 (CE::ZoomIn(f1), CE::ZoomIn(f2)) => {
                     *f1 += *f2;
                 }
-                (CE::ChangeMode(old_mode), CE::ChangeMode(new_mode)) => {
-                    *old_mode = new_mode.clone();
-                }
                 _ => {
                     ret.push(ev.clone());
                 }
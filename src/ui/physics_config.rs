@@ -1,16 +1,27 @@
 use std::ops::RangeInclusive;
 
-use crate::physics::{
-    GlobalPhysics, DEFAULT_FCENTER, DEFAULT_FLINK, DEFAULT_FREPEL, DEFAULT_SLOW_MULT,
+use crate::{
+    physics::{
+        GlobalPhysics, PhysicsParams, DEFAULT_FCENTER, DEFAULT_FLINK, DEFAULT_FREPEL,
+        DEFAULT_FSUBGRAPH_REPEL, DEFAULT_SLOW_MULT,
+    },
+    presets::PhysicsPresets,
 };
 use bevy_egui::egui::{self, CollapsingHeader};
 use eframe::emath::Numeric;
 
-pub fn physics_config_ui(ui: &mut egui::Ui, gphysics: &mut GlobalPhysics) {
+pub fn physics_config_ui(
+    ui: &mut egui::Ui,
+    gphysics: &mut GlobalPhysics,
+    presets: &mut PhysicsPresets,
+) {
     CollapsingHeader::new("Physics configurations")
         .default_open(true)
         .show(ui, |ui| {
-            // log_slider("Connection length", &mut ?, 0f64..=100.0, ui);
+            presets_ui(ui, gphysics, presets);
+            ui.separator();
+
+            let before = gphysics.params();
             log_slider(
                 "Gravity",
                 &mut gphysics.fcenter,
@@ -35,10 +46,76 @@ pub fn physics_config_ui(ui: &mut egui::Ui, gphysics: &mut GlobalPhysics) {
                 0.1 * DEFAULT_FLINK..=10. * DEFAULT_FLINK,
                 ui,
             );
+            ui.add(egui::Label::new("Connection length"));
+            ui.add(egui::Slider::new(&mut gphysics.link_length, 0.0..=500.).clamp_to_range(false));
+            log_slider(
+                "Subgraph repel force",
+                &mut gphysics.fsubgraph_repel,
+                0.1 * DEFAULT_FSUBGRAPH_REPEL..=10. * DEFAULT_FSUBGRAPH_REPEL,
+                ui,
+            );
+            // Hand-tuned parameters no longer match the preset, so a change of the presets file
+            // must not overwrite them
+            if gphysics.params() != before {
+                presets.selected = None;
+            }
+        });
+}
+
+fn presets_ui(ui: &mut egui::Ui, gphysics: &mut GlobalPhysics, presets: &mut PhysicsPresets) {
+    let selected = presets
+        .selected
+        .clone()
+        .unwrap_or_else(|| "custom".to_string());
+    let mut clicked = None;
+    egui::ComboBox::from_label("Preset")
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            for name in presets.names() {
+                if ui
+                    .selectable_label(presets.selected.as_deref() == Some(name), name)
+                    .clicked()
+                {
+                    clicked = Some(name.to_string());
+                }
+            }
         });
+    if let Some(name) = clicked {
+        if let Err(e) = presets.apply(&name, gphysics) {
+            presets.error = Some(e.to_string());
+        }
+    }
+
+    if ui.button("Reset to defaults").clicked() {
+        gphysics.set_params(&PhysicsParams::default());
+        presets.selected = None;
+    }
+
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut presets.name_input);
+        let name = presets.name_input.trim().to_string();
+        if ui
+            .add_enabled(!name.is_empty(), egui::Button::new("Save as"))
+            .clicked()
+        {
+            presets.error = presets
+                .save_as(&name, gphysics.params())
+                .err()
+                .map(|e| e.to_string());
+        }
+    });
+
+    if let Some(e) = &presets.error {
+        ui.colored_label(ui.visuals().error_fg_color, e);
+    }
 }
 
+/// A logarithmic slider that allows typing in values outside of `range`
 fn log_slider<T: Numeric>(text: &str, value: &mut T, range: RangeInclusive<T>, ui: &mut egui::Ui) {
     ui.add(egui::Label::new(text));
-    ui.add(egui::Slider::new(value, range).logarithmic(true));
+    ui.add(
+        egui::Slider::new(value, range)
+            .logarithmic(true)
+            .clamp_to_range(false),
+    );
 }
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+
+use crate::{
+    debug_overlay::{PhysicsDebug, PhysicsStats},
+    physics::GlobalPhysics,
+};
+use bevy_egui::egui::{self, CollapsingHeader};
+
+const PLOT_HEIGHT: f32 = 40.;
+
+pub fn physics_debug_ui(
+    ui: &mut egui::Ui,
+    gphysics: &mut GlobalPhysics,
+    debug: &mut PhysicsDebug,
+    stats: &PhysicsStats,
+) {
+    CollapsingHeader::new("Physics debug")
+        .default_open(false)
+        .show(ui, |ui| {
+            ui.checkbox(&mut gphysics.parallel, "Parallel update");
+            ui.checkbox(&mut debug.enabled, "Show overlay");
+            ui.add_enabled_ui(debug.enabled, |ui| {
+                ui.checkbox(&mut debug.show_velocity, "Velocities (yellow)");
+                ui.checkbox(
+                    &mut debug.show_forces,
+                    "Forces: center (green), subgraph (teal), repel (red), link (cyan), total \
+                     (white)",
+                );
+                ui.checkbox(&mut debug.show_springs, "Spring tension");
+                ui.add(
+                    egui::Slider::new(&mut debug.force_scale, 0.0001..=10.)
+                        .logarithmic(true)
+                        .text("Force scale"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut debug.velocity_scale, 0.01..=10.)
+                        .logarithmic(true)
+                        .text("Velocity scale"),
+                );
+            });
+            plot_ui("Kinetic energy", &stats.kinetic_energy, ui);
+            plot_ui("Max velocity", &stats.max_speed, ui);
+        });
+}
+
+/// Draw a line plot of `values`, scaled to fit between zero and the maximum value
+fn plot_ui(label: &str, values: &VecDeque<f32>, ui: &mut egui::Ui) {
+    let latest = values.back().copied().unwrap_or(0.);
+    let max = values.iter().copied().fold(0., f32::max);
+    ui.label(format!("{label}: {latest:.1} (max {max:.1})"));
+
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), PLOT_HEIGHT),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_stroke(rect, 0., ui.visuals().widgets.noninteractive.bg_stroke);
+
+    if values.len() < 2 || max <= 0. {
+        return;
+    }
+    let dx = rect.width() / (values.len() - 1) as f32;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(idx, v)| {
+            egui::pos2(
+                rect.left() + idx as f32 * dx,
+                rect.bottom() - v / max * rect.height(),
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1., ui.visuals().text_color()),
+    ));
+}